@@ -0,0 +1,276 @@
+use crate::cue::{
+    Beat, BeatEvent, Cue, EventOffset, JumpModeChange, JumpRequirement, PauseEventBehaviour,
+    TempoCurve, TimeSignature,
+};
+
+/// Builds a cue bar by bar. Events are collected for the next beat added, so
+/// `.tempo(120).mark("A").bars(8, (4, 4))` starts section A at 120 BPM.
+#[derive(Clone, Default)]
+pub struct CueBuilder {
+    cue: Cue,
+    events: Vec<BeatEvent>,
+    time_signature: Option<TimeSignature>,
+    tempo: usize,
+    curve: TempoCurve,
+    section_start: usize,
+}
+
+impl CueBuilder {
+    pub fn new(name: &str) -> CueBuilder {
+        let mut builder = CueBuilder {
+            tempo: 120,
+            ..Default::default()
+        };
+        builder.cue.metadata.name = name.to_string();
+        builder
+    }
+
+    pub fn ident(mut self, human_ident: &str) -> Self {
+        self.cue.metadata.human_ident = human_ident.to_string();
+        self
+    }
+
+    /// Adds `count` bars of `meter`, given as (numerator, denominator).
+    pub fn bars(mut self, count: usize, meter: (u8, u8)) -> Self {
+        let time_signature = TimeSignature {
+            numerator: meter.0.max(1),
+            denominator: meter.1,
+            grouping: None,
+        };
+        if self.time_signature.as_ref() != Some(&time_signature) {
+            self.events.push(BeatEvent::TimeSignatureEvent {
+                numerator: time_signature.numerator,
+                denominator: time_signature.denominator,
+                grouping: None,
+            });
+        }
+        let beats = count * time_signature.numerator as usize;
+        self.time_signature = Some(time_signature);
+        self.beats(beats)
+    }
+
+    /// Adds `count` beats in the current meter, 4/4 if none has been set.
+    pub fn beats(mut self, count: usize) -> Self {
+        if self.time_signature.is_none() && count > 0 {
+            self = self.bars(0, (4, 4));
+        }
+        self.section_start = self.cue.beats.len();
+        for _ in 0..count {
+            self.cue.beats.push(Beat {
+                events: std::mem::take(&mut self.events),
+                ..Beat::empty()
+            });
+        }
+        self
+    }
+
+    /// Sets the tempo from the next beat on. Tempos below 1 BPM are raised to 1.
+    pub fn tempo(mut self, bpm: usize) -> Self {
+        let bpm = bpm.max(1);
+        self.tempo = bpm;
+        self.event(BeatEvent::TempoChangeEvent { tempo: bpm })
+    }
+
+    /// Curve used by the following `ramp_to` calls.
+    pub fn curve(mut self, curve: TempoCurve) -> Self {
+        self.curve = curve;
+        self
+    }
+
+    /// Changes gradually from the current tempo to `bpm` over the next `over_beats` beats. Both
+    /// are raised to at least 1.
+    pub fn ramp_to(self, bpm: usize, over_beats: usize) -> Self {
+        let start_tempo = self.tempo;
+        self.ramp(start_tempo, bpm, over_beats)
+    }
+
+    /// Like `ramp_to`, but jumps to `from` first as part of the same event.
+    pub fn ramp(mut self, from: usize, bpm: usize, over_beats: usize) -> Self {
+        let (bpm, over_beats) = (bpm.max(1), over_beats.max(1));
+        let curve = self.curve;
+        self.tempo = bpm;
+        self.event(BeatEvent::GradualTempoChangeEvent {
+            start_tempo: from.max(1),
+            end_tempo: bpm,
+            length: over_beats,
+            curve,
+        })
+    }
+
+    pub fn mark(self, label: &str) -> Self {
+        self.event(BeatEvent::RehearsalMarkEvent {
+            label: label.to_string(),
+        })
+    }
+
+    pub fn play(self, channel_idx: usize, clip_idx: usize) -> Self {
+        self.event(BeatEvent::PlaybackEvent {
+            channel_idx,
+            clip_idx,
+            sample: 0,
+            offset: EventOffset::Start,
+        })
+    }
+
+    pub fn stop(self, channel_idx: usize) -> Self {
+        self.event(BeatEvent::PlaybackStopEvent {
+            channel_idx,
+            offset: EventOffset::Start,
+        })
+    }
+
+    /// Repeats the beats added by the last `bars` or `beats` call for as long as the VLT is on.
+    /// Does nothing if that call added no beats.
+    pub fn vamp(mut self) -> Self {
+        let destination = self.section_start;
+        if destination == self.cue.beats.len() {
+            return self;
+        }
+        if let Some(beat) = self.cue.beats.last_mut() {
+            beat.events.push(BeatEvent::JumpEvent {
+                destination,
+                requirement: JumpRequirement::JumpModeOn,
+                when_jumped: JumpModeChange::None,
+                when_passed: JumpModeChange::None,
+            });
+        }
+        self
+    }
+
+    /// Plays the beats added by the last `bars` or `beats` call `times` more times. Does nothing if
+    /// that call added no beats.
+    pub fn repeat(mut self, times: usize) -> Self {
+        let destination = self.section_start;
+        if destination == self.cue.beats.len() {
+            return self;
+        }
+        if let Some(beat) = self.cue.beats.last_mut() {
+            beat.events.push(BeatEvent::JumpEvent {
+                destination,
+                requirement: JumpRequirement::Repeat { times },
+                when_jumped: JumpModeChange::None,
+                when_passed: JumpModeChange::None,
+            });
+        }
+        self
+    }
+
+    /// Pauses at the end of the last beat added.
+    pub fn pause(mut self, behaviour: PauseEventBehaviour) -> Self {
+        if let Some(beat) = self.cue.beats.last_mut() {
+            beat.events.push(BeatEvent::PauseEvent { behaviour });
+        }
+        self
+    }
+
+    /// Adds any event to the next beat.
+    pub fn event(mut self, event: BeatEvent) -> Self {
+        self.events.push(event);
+        self
+    }
+
+    /// Numbers the bars and sets beat lengths from the tempo events. Events added after the last
+    /// beat go on the last beat.
+    pub fn build(mut self) -> Cue {
+        if let Some(beat) = self.cue.beats.last_mut() {
+            beat.events.append(&mut self.events);
+        }
+        self.cue.reorder_numbers();
+        self.cue.recalculate_tempo_changes();
+        self.cue
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sections() {
+        let cue = CueBuilder::new("Ballad")
+            .ident("2.4")
+            .tempo(60)
+            .play(0, 3)
+            .bars(2, (4, 4))
+            .mark("A")
+            .ramp_to(120, 4)
+            .bars(1, (6, 8))
+            .vamp()
+            .stop(0)
+            .beats(2)
+            .pause(PauseEventBehaviour::NextCue)
+            .build();
+        assert_eq!(cue.metadata.human_ident, "2.4");
+        assert_eq!(cue.beats.len(), 16);
+        assert_eq!((cue.beats[8].bar_number, cue.beats[8].count), (3, 1));
+        assert_eq!((cue.beats[14].bar_number, cue.beats[14].count), (4, 1));
+        assert_eq!(cue.beats[0].length, 1000000);
+        assert_eq!(cue.beats[8].length, 1000000);
+        assert_eq!(cue.beats[12].length, 500000);
+        assert_eq!(cue.time_signature_at(15).to_string(), "6/8");
+        assert!(matches!(
+            cue.beats[13].events[..],
+            [BeatEvent::JumpEvent { destination: 8, .. }]
+        ));
+        assert!(matches!(
+            cue.beats[14].events[..],
+            [BeatEvent::PlaybackStopEvent { channel_idx: 0, .. }]
+        ));
+        assert!(matches!(
+            cue.beats[15].events[..],
+            [BeatEvent::PauseEvent {
+                behaviour: PauseEventBehaviour::NextCue
+            }]
+        ));
+        assert!(cue.lint().is_empty());
+    }
+
+    #[test]
+    fn default_meter() {
+        let cue = CueBuilder::new("").beats(6).mark("End").build();
+        assert_eq!(cue.beats[4].bar_number, 2);
+        assert!(cue.beats.iter().all(|b| b.length == 500000));
+        assert!(matches!(
+            &cue.beats[5].events[..],
+            [BeatEvent::RehearsalMarkEvent { label }] if label == "End"
+        ));
+    }
+
+    #[test]
+    fn empty_sections() {
+        let cue = CueBuilder::new("")
+            .bars(2, (4, 4))
+            .bars(0, (4, 4))
+            .vamp()
+            .beats(0)
+            .repeat(2)
+            .build();
+        assert!(cue.beats.iter().all(|b| {
+            b.events_filter(|e| matches!(e, BeatEvent::JumpEvent { .. }))
+                .is_empty()
+        }));
+        assert!(cue.lint().is_empty());
+        assert!(
+            CueBuilder::new("")
+                .vamp()
+                .repeat(1)
+                .build()
+                .beats
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn out_of_range_tempos() {
+        let cue = CueBuilder::new("").tempo(0).bars(1, (4, 4)).build();
+        assert!(cue.beats.iter().all(|b| b.length == 60000000));
+        let cue = CueBuilder::new("")
+            .ramp_to(0, 0)
+            .beats(2)
+            .ramp_to(60, 0)
+            .beats(2)
+            .build();
+        let lengths: Vec<usize> = cue.beats.iter().map(|b| b.length).collect();
+        assert_eq!(lengths, [500000, 60000000, 60000000, 1000000]);
+    }
+}
@@ -4,6 +4,7 @@ use std::{error::Error, fmt::Display};
 use serde::{Deserialize, Serialize};
 
 use crate::cue::JumpModeChange;
+use crate::timecode::TimecodePlayrateMode;
 use crate::{cue::Cue, show::Show};
 
 #[derive(Clone, Deserialize, Serialize, Debug, PartialEq)]
@@ -24,6 +25,7 @@ pub enum ControlCommand {
     SetChannelMute(usize, bool),
     ChangeJumpMode(JumpModeChange),
     ChangePlayrate(usize),
+    ChangeTimecodePlayrateMode(TimecodePlayrateMode),
 }
 
 impl Display for ControlCommand {
@@ -45,6 +47,9 @@ impl Display for ControlCommand {
             ControlCommand::SetChannelMute(..) => write!(f, "SetChannelMute"),
             ControlCommand::ChangeJumpMode(..) => write!(f, "ChangeJumpMode"),
             ControlCommand::ChangePlayrate(..) => write!(f, "ChangePlayrate"),
+            ControlCommand::ChangeTimecodePlayrateMode(..) => {
+                write!(f, "ChangeTimecodePlayrateMode")
+            }
         }
     }
 }
@@ -69,8 +74,6 @@ impl fmt::Display for CommandError {
 
 impl Error for CommandError {
     fn source(&self) -> Option<&(dyn Error + 'static)> {
-        match self {
-            _ => None,
-        }
+        None
     }
 }
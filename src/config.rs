@@ -1,5 +1,5 @@
 use serde::{Deserialize, Serialize};
-use std::{collections::HashMap, default, fmt::Display};
+use std::{collections::HashMap, fmt::Display};
 
 #[derive(Default, Debug, Clone, Serialize, Deserialize)]
 pub enum BootProgramOrder {
@@ -39,7 +39,7 @@ impl Default for ChannelsConfiguration {
             .into_iter()
             .chain(
                 (3..33)
-                    .map(|i| ChannelConfiguration::new(i))
+                    .map(ChannelConfiguration::new)
                     .collect::<Vec<ChannelConfiguration>>(),
             )
             .collect(),
@@ -1,10 +1,19 @@
 use std::{
+    collections::BTreeMap,
     fmt::{self, Display},
-    slice::Iter,
 };
 
 use serde::{Deserialize, Serialize};
 
+use crate::{
+    lint::{self, CueDiagnostic},
+    mtc::{SYSEX_END, SYSEX_START},
+    osc::OscMessage,
+    simulate::{Simulation, Simulator},
+    timecode::{FrameRate, TimecodeInstant, TimecodeParseError, UserBits},
+    timemap::TimeMap,
+};
+
 #[derive(Clone, Serialize, Deserialize, Default, PartialEq)]
 pub struct Beat {
     pub count: u8,
@@ -39,9 +48,66 @@ impl Beat {
         self.events.iter().filter(|e| filter(e)).cloned().collect()
     }
 
+    /// Microseconds after the start of the beat that each event fires, in event order.
+    pub fn event_offsets_us(&self) -> Vec<u64> {
+        self.events
+            .iter()
+            .map(|event| event.offset().offset_us(self.length))
+            .collect()
+    }
+
     pub fn tempo(&self) -> usize {
         (60000000.0 / self.length as f32).round() as usize
     }
+
+    pub fn time_signature(&self) -> Option<TimeSignature> {
+        self.events.iter().find_map(|event| match event {
+            BeatEvent::TimeSignatureEvent {
+                numerator,
+                denominator,
+                grouping,
+            } => Some(TimeSignature {
+                numerator: *numerator,
+                denominator: *denominator,
+                grouping: grouping.clone(),
+            }),
+            _ => None,
+        })
+    }
+
+    fn subdivision_event(
+        &self,
+        wanted: SubdivisionScope,
+    ) -> Option<(Subdivision, Option<Vec<AccentLevel>>)> {
+        self.events.iter().find_map(|event| match event {
+            BeatEvent::SubdivisionEvent {
+                subdivision,
+                scope,
+                accents,
+            } if *scope == wanted => Some((*subdivision, accents.clone())),
+            _ => None,
+        })
+    }
+
+    /// Clicks of this beat under the given subdivision and accents, no subdivision if `None`.
+    fn clicks(
+        &self,
+        subdivision: Option<(Subdivision, Option<Vec<AccentLevel>>)>,
+    ) -> Vec<AccentLevel> {
+        let (subdivision, accents) = subdivision.unwrap_or_default();
+        let first = if self.count == 1 {
+            AccentLevel::Downbeat
+        } else {
+            AccentLevel::Beat
+        };
+        (0..subdivision.clicks() as usize)
+            .map(|click| match accents.as_ref().and_then(|a| a.get(click)) {
+                Some(level) => *level,
+                None if click == 0 => first,
+                None => AccentLevel::Subdivision,
+            })
+            .collect()
+    }
 }
 
 #[derive(Clone, Serialize, Deserialize, PartialEq)]
@@ -49,6 +115,10 @@ pub enum JumpRequirement {
     JumpModeOn,
     JumpModeOff,
     None,
+    /// Jumps `times` times, then falls through, whatever the VLT.
+    Repeat {
+        times: usize,
+    },
 }
 
 impl Display for JumpRequirement {
@@ -57,6 +127,44 @@ impl Display for JumpRequirement {
             JumpRequirement::JumpModeOn => write!(f, "VLT On"),
             JumpRequirement::JumpModeOff => write!(f, "VLT Off"),
             JumpRequirement::None => write!(f, "None"),
+            JumpRequirement::Repeat { times } => write!(f, "Repeat {times}x"),
+        }
+    }
+}
+
+/// Jumps taken so far by the counted repeats of a cue, by beat index. Nothing resets the counters
+/// automatically: whoever plays the cue must call `reset` when it loads or restarts one, as
+/// `Simulator::run` does by starting from a new set.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct RepeatCounters {
+    taken: BTreeMap<usize, usize>,
+}
+
+impl RepeatCounters {
+    pub fn new() -> RepeatCounters {
+        RepeatCounters::default()
+    }
+
+    pub fn reset(&mut self) {
+        self.taken.clear();
+    }
+
+    /// Repeats taken from the jump on the given beat since it last fell through.
+    pub fn taken(&self, beat_idx: usize) -> usize {
+        self.taken.get(&beat_idx).copied().unwrap_or(0)
+    }
+
+    /// Decides a counted repeat at the end of the given beat. Jumps until it has jumped `times`
+    /// times, then falls through once and starts counting again, so a repeat inside a longer
+    /// loop plays in full every time round.
+    pub fn repeat(&mut self, beat_idx: usize, times: usize) -> bool {
+        let taken = self.taken(beat_idx);
+        if taken < times {
+            self.taken.insert(beat_idx, taken + 1);
+            true
+        } else {
+            self.taken.remove(&beat_idx);
+            false
         }
     }
 }
@@ -137,20 +245,38 @@ pub enum BeatEvent {
         start_tempo: usize,
         end_tempo: usize,
         length: usize,
+        #[serde(default)]
+        curve: TempoCurve,
     },
     PlaybackEvent {
         channel_idx: usize,
         clip_idx: usize,
         sample: i32,
+        #[serde(default)]
+        offset: EventOffset,
     },
     PlaybackStopEvent {
         channel_idx: usize,
+        #[serde(default)]
+        offset: EventOffset,
     },
+    /// Show files may give the time either as `h`/`m`/`s`/`f` or as a `timecode` string such as
+    /// `"01:00:00:00"`, which takes precedence. See `BeatEvent::timecode`.
     TimecodeEvent {
+        #[serde(default)]
         h: usize,
+        #[serde(default)]
         m: usize,
+        #[serde(default)]
         s: usize,
+        #[serde(default)]
         f: usize,
+        #[serde(default)]
+        timecode: Option<String>,
+        #[serde(default)]
+        user_bits: UserBits,
+        #[serde(default)]
+        offset: EventOffset,
     },
     RehearsalMarkEvent {
         label: String,
@@ -158,6 +284,32 @@ pub enum BeatEvent {
     PauseEvent {
         behaviour: PauseEventBehaviour,
     },
+    TimeSignatureEvent {
+        numerator: u8,
+        denominator: u8,
+        /// Beats per group within the bar, e.g. `[2, 2, 3]` for 7/8 felt as 2+2+3.
+        grouping: Option<Vec<u8>>,
+    },
+    SubdivisionEvent {
+        subdivision: Subdivision,
+        scope: SubdivisionScope,
+        /// Level of each click in the beat, replacing the default of an accent on the first click.
+        accents: Option<Vec<AccentLevel>>,
+    },
+    MidiEvent {
+        /// Name of the MIDI output the message is sent to.
+        output: String,
+        message: MidiMessage,
+        #[serde(default)]
+        offset: EventOffset,
+    },
+    OscEvent {
+        /// Name of the OSC destination the message is sent to.
+        target: String,
+        message: OscMessage,
+        #[serde(default)]
+        offset: EventOffset,
+    },
 }
 
 impl BeatEvent {
@@ -171,6 +323,334 @@ impl BeatEvent {
             BeatEvent::TimecodeEvent { .. } => "Timecode",
             BeatEvent::RehearsalMarkEvent { .. } => "Rehearsal Mark",
             BeatEvent::PauseEvent { .. } => "Pause Event",
+            BeatEvent::TimeSignatureEvent { .. } => "Time Signature",
+            BeatEvent::SubdivisionEvent { .. } => "Subdivision",
+            BeatEvent::MidiEvent { .. } => "MIDI",
+            BeatEvent::OscEvent { .. } => "OSC",
+        }
+    }
+
+    /// Where in its beat the event fires.
+    pub fn offset(&self) -> EventOffset {
+        match self {
+            BeatEvent::PlaybackEvent { offset, .. }
+            | BeatEvent::PlaybackStopEvent { offset, .. }
+            | BeatEvent::TimecodeEvent { offset, .. }
+            | BeatEvent::MidiEvent { offset, .. }
+            | BeatEvent::OscEvent { offset, .. } => *offset,
+            _ => EventOffset::Start,
+        }
+    }
+
+    /// Time set by a timecode event at the show's frame rate, `None` for other events.
+    pub fn timecode(
+        &self,
+        frame_rate: FrameRate,
+    ) -> Option<Result<TimecodeInstant, TimecodeParseError>> {
+        let BeatEvent::TimecodeEvent {
+            h,
+            m,
+            s,
+            f,
+            timecode,
+            user_bits,
+            ..
+        } = self
+        else {
+            return None;
+        };
+        let time = match timecode {
+            Some(timecode) => TimecodeInstant::parse(timecode, frame_rate),
+            None => {
+                let mut time = TimecodeInstant::new(frame_rate);
+                time.set_time(*h, *m, *s, *f);
+                Ok(time)
+            }
+        };
+        Some(time.map(|time| TimecodeInstant {
+            user_bits: *user_bits,
+            ..time
+        }))
+    }
+}
+
+/// A MIDI message sent by a `BeatEvent::MidiEvent`. Channels count from 0.
+#[derive(Clone, Serialize, Deserialize, PartialEq, Eq, Debug)]
+pub enum MidiMessage {
+    NoteOn {
+        channel: u8,
+        note: u8,
+        velocity: u8,
+    },
+    NoteOff {
+        channel: u8,
+        note: u8,
+        velocity: u8,
+    },
+    ControlChange {
+        channel: u8,
+        controller: u8,
+        value: u8,
+    },
+    ProgramChange {
+        channel: u8,
+        program: u8,
+    },
+    /// System exclusive message body, without the leading 0xF0 and trailing 0xF7.
+    SysEx(Vec<u8>),
+}
+
+impl Display for MidiMessage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MidiMessage::NoteOn {
+                channel,
+                note,
+                velocity,
+            } => write!(f, "Note on ch {} note {note} vel {velocity}", channel + 1),
+            MidiMessage::NoteOff { channel, note, .. } => {
+                write!(f, "Note off ch {} note {note}", channel + 1)
+            }
+            MidiMessage::ControlChange {
+                channel,
+                controller,
+                value,
+            } => write!(f, "CC ch {} #{controller} = {value}", channel + 1),
+            MidiMessage::ProgramChange { channel, program } => {
+                write!(f, "Program change ch {} to {program}", channel + 1)
+            }
+            MidiMessage::SysEx(data) => write!(f, "SysEx ({} bytes)", data.len()),
+        }
+    }
+}
+
+impl MidiMessage {
+    /// Bytes on the wire. Channels and data bytes out of range are masked to fit.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        match self {
+            MidiMessage::NoteOn {
+                channel,
+                note,
+                velocity,
+            } => vec![0x90 | (channel & 0x0F), note & 0x7F, velocity & 0x7F],
+            MidiMessage::NoteOff {
+                channel,
+                note,
+                velocity,
+            } => vec![0x80 | (channel & 0x0F), note & 0x7F, velocity & 0x7F],
+            MidiMessage::ControlChange {
+                channel,
+                controller,
+                value,
+            } => vec![0xB0 | (channel & 0x0F), controller & 0x7F, value & 0x7F],
+            MidiMessage::ProgramChange { channel, program } => {
+                vec![0xC0 | (channel & 0x0F), program & 0x7F]
+            }
+            MidiMessage::SysEx(data) => {
+                let mut bytes = vec![SYSEX_START];
+                bytes.extend(data.iter().map(|byte| byte & 0x7F));
+                bytes.push(SYSEX_END);
+                bytes
+            }
+        }
+    }
+}
+
+/// Position of an event within its beat.
+#[derive(Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Debug, Default)]
+pub enum EventOffset {
+    #[default]
+    Start,
+    /// Fraction of the beat, e.g. 1/2 for the "and".
+    Fraction {
+        numerator: u32,
+        denominator: u32,
+    },
+    Microseconds(u64),
+}
+
+impl Display for EventOffset {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EventOffset::Start => write!(f, "On the beat"),
+            EventOffset::Fraction {
+                numerator,
+                denominator,
+            } => write!(f, "{numerator}/{denominator} beat"),
+            EventOffset::Microseconds(us) => write!(f, "{us} us"),
+        }
+    }
+}
+
+impl EventOffset {
+    /// Microseconds after the start of a beat of `beat_length`, never past its end.
+    pub fn offset_us(&self, beat_length: usize) -> u64 {
+        let beat_length = beat_length as u64;
+        match self {
+            EventOffset::Start => 0,
+            EventOffset::Fraction {
+                numerator,
+                denominator,
+            } => beat_length * *numerator as u64 / (*denominator).max(1) as u64,
+            EventOffset::Microseconds(us) => *us,
+        }
+        .min(beat_length)
+    }
+}
+
+#[derive(Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Debug, Default)]
+pub enum Subdivision {
+    #[default]
+    None,
+    Eighths,
+    Triplets,
+    Sixteenths,
+    /// Any number of evenly spaced clicks per beat.
+    Custom(u8),
+}
+
+impl Display for Subdivision {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Subdivision::None => write!(f, "None"),
+            Subdivision::Eighths => write!(f, "Eighths"),
+            Subdivision::Triplets => write!(f, "Triplets"),
+            Subdivision::Sixteenths => write!(f, "Sixteenths"),
+            Subdivision::Custom(clicks) => write!(f, "{clicks} per beat"),
+        }
+    }
+}
+
+impl Subdivision {
+    pub fn clicks(&self) -> u8 {
+        match self {
+            Subdivision::None => 1,
+            Subdivision::Eighths => 2,
+            Subdivision::Triplets => 3,
+            Subdivision::Sixteenths => 4,
+            Subdivision::Custom(clicks) => (*clicks).max(1),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Debug)]
+pub enum SubdivisionScope {
+    /// Only the beat carrying the event.
+    Beat,
+    /// Every beat until the next section subdivision event.
+    Section,
+}
+
+#[derive(Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Debug)]
+pub enum AccentLevel {
+    Downbeat,
+    Beat,
+    Subdivision,
+    Silent,
+}
+
+impl Display for AccentLevel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AccentLevel::Downbeat => write!(f, "Downbeat"),
+            AccentLevel::Beat => write!(f, "Beat"),
+            AccentLevel::Subdivision => write!(f, "Subdivision"),
+            AccentLevel::Silent => write!(f, "Silent"),
+        }
+    }
+}
+
+/// Shape of a gradual tempo change from its start tempo to its end tempo.
+#[derive(Clone, Copy, Serialize, Deserialize, PartialEq, Debug, Default)]
+pub enum TempoCurve {
+    /// Beat length changes by the same amount every beat.
+    #[default]
+    LinearPeriod,
+    /// Tempo changes by the same number of BPM every beat.
+    LinearTempo,
+    /// Tempo changes by the same ratio every beat.
+    Exponential,
+    EaseIn,
+    EaseOut,
+    SCurve,
+}
+
+impl Display for TempoCurve {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TempoCurve::LinearPeriod => write!(f, "Linear period"),
+            TempoCurve::LinearTempo => write!(f, "Linear tempo"),
+            TempoCurve::Exponential => write!(f, "Exponential"),
+            TempoCurve::EaseIn => write!(f, "Ease in"),
+            TempoCurve::EaseOut => write!(f, "Ease out"),
+            TempoCurve::SCurve => write!(f, "S-curve"),
+        }
+    }
+}
+
+impl TempoCurve {
+    /// Tempo in BPM at `progress`, from 0.0 at the start of the change to 1.0 at its end.
+    pub fn tempo_at(&self, start_tempo: usize, end_tempo: usize, progress: f64) -> f64 {
+        let start = start_tempo.max(1) as f64;
+        let end = end_tempo.max(1) as f64;
+        let x = progress.clamp(0.0, 1.0);
+        let eased = match self {
+            TempoCurve::LinearPeriod => {
+                return 1.0 / (1.0 / start + (1.0 / end - 1.0 / start) * x);
+            }
+            TempoCurve::Exponential => return start * (end / start).powf(x),
+            TempoCurve::LinearTempo => x,
+            TempoCurve::EaseIn => x * x,
+            TempoCurve::EaseOut => 1.0 - (1.0 - x) * (1.0 - x),
+            TempoCurve::SCurve => x * x * (3.0 - 2.0 * x),
+        };
+        start + (end - start) * eased
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize, PartialEq, Debug)]
+pub struct TimeSignature {
+    pub numerator: u8,
+    pub denominator: u8,
+    pub grouping: Option<Vec<u8>>,
+}
+
+impl Default for TimeSignature {
+    fn default() -> Self {
+        Self {
+            numerator: 4,
+            denominator: 4,
+            grouping: None,
+        }
+    }
+}
+
+impl Display for TimeSignature {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.numerator, self.denominator)?;
+        if let Some(grouping) = &self.grouping {
+            let groups: Vec<String> = grouping.iter().map(|g| g.to_string()).collect();
+            write!(f, " ({})", groups.join("+"))?;
+        }
+        Ok(())
+    }
+}
+
+impl TimeSignature {
+    /// Counts on which a group starts, e.g. `[1, 3, 5]` for 2+2+3. Without a grouping, only the
+    /// downbeat starts a group.
+    pub fn group_starts(&self) -> Vec<u8> {
+        match &self.grouping {
+            Some(grouping) => grouping
+                .iter()
+                .scan(1, |count, len| {
+                    let start = *count;
+                    *count += len;
+                    Some(start)
+                })
+                .filter(|count| *count <= self.numerator)
+                .collect(),
+            None => vec![1],
         }
     }
 }
@@ -221,6 +701,7 @@ impl Cue {
             channel_idx: 0,
             clip_idx: 0,
             sample: 0,
+            offset: EventOffset::Start,
         });
         br
     }
@@ -251,6 +732,7 @@ impl Cue {
             channel_idx: 0,
             clip_idx: 0,
             sample: 0,
+            offset: EventOffset::Start,
         });
         br
     }
@@ -266,10 +748,85 @@ impl Cue {
         self.beats.clone()
     }
 
+    /// Beat and bar timing of the cue as it stands now, built from scratch in O(n) on every call.
+    ///
+    /// The cue does not cache the map or track changes to it. Callers that look times up
+    /// repeatedly should keep the map and call `TimeMap::rebuild` themselves whenever they change
+    /// the beats or tempo events, e.g. after `recalculate_tempo_changes` or `reorder_numbers`.
+    pub fn time_map(&self) -> TimeMap {
+        TimeMap::new(self)
+    }
+
+    /// Structural problems in the cue, such as jumps past the end or loops that never finish.
+    pub fn lint(&self) -> Vec<CueDiagnostic> {
+        lint::lint(self)
+    }
+
+    /// Plays the cue through with the VLT initially set to `vlt` and no operator input.
+    pub fn simulate(&self, vlt: bool) -> Simulation {
+        Simulator::new(vlt).run(self)
+    }
+
+    /// Time signature in effect at the given beat, 4/4 before the first time signature event.
+    pub fn time_signature_at(&self, beat_idx: usize) -> TimeSignature {
+        self.beats
+            .get(..=beat_idx)
+            .unwrap_or(&self.beats)
+            .iter()
+            .rev()
+            .find_map(|beat| beat.time_signature())
+            .unwrap_or_default()
+    }
+
+    /// Clicks of the given beat, evenly spaced over its length. A beat-scoped subdivision event
+    /// overrides the section subdivision in effect.
+    pub fn click_pattern(&self, beat_idx: usize) -> Vec<AccentLevel> {
+        let Some(beat) = self.beats.get(beat_idx) else {
+            return vec![];
+        };
+        let section = || {
+            self.beats[..=beat_idx]
+                .iter()
+                .rev()
+                .find_map(|beat| beat.subdivision_event(SubdivisionScope::Section))
+        };
+        beat.clicks(
+            beat.subdivision_event(SubdivisionScope::Beat)
+                .or_else(section),
+        )
+    }
+
+    /// Clicks of every beat as `click_pattern` gives them, resolved in a single pass over the cue.
+    pub fn click_patterns(&self) -> Vec<Vec<AccentLevel>> {
+        let mut section = None;
+        self.beats
+            .iter()
+            .map(|beat| {
+                if let Some(event) = beat.subdivision_event(SubdivisionScope::Section) {
+                    section = Some(event);
+                }
+                beat.clicks(
+                    beat.subdivision_event(SubdivisionScope::Beat)
+                        .or_else(|| section.clone()),
+                )
+            })
+            .collect()
+    }
+
+    /// Renumbers bars and counts. Cues with time signature events are numbered from the meter,
+    /// starting a new bar at every time signature event; others keep their existing bar breaks.
     pub fn reorder_numbers(&mut self) {
         if self.beats.is_empty() {
             return;
         }
+        if self
+            .beats
+            .iter()
+            .any(|beat| beat.time_signature().is_some())
+        {
+            self.number_from_time_signatures();
+            return;
+        }
         let mut bar = if self.beats[0].bar_number == 0 { 0 } else { 1 };
         let mut count = 1;
         let mut prev_bar = bar;
@@ -288,30 +845,77 @@ impl Cue {
         }
     }
 
+    fn number_from_time_signatures(&mut self) {
+        let mut numerator = TimeSignature::default().numerator;
+        let mut bar = 0;
+        let mut count = 0;
+        for beat in &mut self.beats {
+            let time_signature = beat.time_signature();
+            if let Some(time_signature) = &time_signature {
+                numerator = time_signature.numerator.max(1);
+            }
+            if count == 0 || count >= numerator || time_signature.is_some() {
+                bar += 1;
+                count = 0;
+            }
+            count += 1;
+            beat.bar_number = bar;
+            beat.count = count;
+        }
+    }
+
+    /// Sets beat lengths from tempo events. Gradual changes follow their curve over `length`
+    /// beats and reach the end tempo on the beat after.
     pub fn recalculate_tempo_changes(&mut self) {
         let mut beat_length = 1000000 * 60 / 120;
         let mut beats_left_in_change = 0;
         let mut accelerator: f32 = 0.0;
+        // Curved changes as (start tempo, end tempo, length, curve, beats done).
+        let mut curved: Option<(usize, usize, usize, TempoCurve, usize)> = None;
         for beat in &mut self.beats {
             if let Some(BeatEvent::TempoChangeEvent { tempo }) = beat
                 .events_filter(|f| matches!(f, BeatEvent::TempoChangeEvent { .. }))
-                .get(0)
+                .first()
             {
-                beat_length = 1000000 * 60 / tempo;
+                beat_length = 1000000 * 60 / (*tempo).max(1);
                 accelerator = 0.0;
+                curved = None;
             }
             if let Some(BeatEvent::GradualTempoChangeEvent {
                 start_tempo,
                 end_tempo,
                 length,
+                curve,
             }) = beat
                 .events_filter(|f| matches!(f, BeatEvent::GradualTempoChangeEvent { .. }))
-                .get(0)
+                .first()
             {
+                // Zeroes can come from a show file; `lint` reports them.
+                let start_tempo = (*start_tempo).max(1);
+                let end_tempo = (*end_tempo).max(1);
+                let length = (*length).max(1);
                 beat_length = 1000000 * 60 / start_tempo;
-                accelerator = (60000000.0 / *end_tempo as f32 - 60000000.0 / *start_tempo as f32)
-                    / *length as f32;
-                beats_left_in_change = *length;
+                if *curve == TempoCurve::LinearPeriod {
+                    // Kept as it always was, so existing cues keep their exact beat lengths.
+                    accelerator = (60000000.0 / end_tempo as f32 - 60000000.0 / start_tempo as f32)
+                        / length as f32;
+                    beats_left_in_change = length;
+                    curved = None;
+                } else {
+                    accelerator = 0.0;
+                    beats_left_in_change = 0;
+                    curved = Some((start_tempo, end_tempo, length, *curve, 0));
+                }
+            }
+            if let Some((start_tempo, end_tempo, length, curve, done)) = &mut curved {
+                let tempo = curve.tempo_at(*start_tempo, *end_tempo, *done as f64 / *length as f64);
+                beat.length = (60000000.0 / tempo).round() as usize;
+                *done += 1;
+                if done == length {
+                    beat_length = 1000000 * 60 / *end_tempo;
+                    curved = None;
+                }
+                continue;
             }
             beat.length = beat_length;
             beat_length = (beat_length as f32 + accelerator).round() as usize;
@@ -322,3 +926,326 @@ impl Cue {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn time_signature(numerator: u8, denominator: u8) -> BeatEvent {
+        BeatEvent::TimeSignatureEvent {
+            numerator,
+            denominator,
+            grouping: None,
+        }
+    }
+
+    #[test]
+    fn numbering_follows_time_signatures() {
+        let mut cue = Cue::example();
+        cue.beats.truncate(22);
+        cue.beats[2].events.push(time_signature(7, 8));
+        cue.beats[16].events.push(time_signature(5, 4));
+        cue.reorder_numbers();
+        let numbers: Vec<(usize, u8)> = cue.beats.iter().map(|b| (b.bar_number, b.count)).collect();
+        assert_eq!(
+            numbers,
+            [
+                (1, 1),
+                (1, 2),
+                (2, 1),
+                (2, 2),
+                (2, 3),
+                (2, 4),
+                (2, 5),
+                (2, 6),
+                (2, 7),
+                (3, 1),
+                (3, 2),
+                (3, 3),
+                (3, 4),
+                (3, 5),
+                (3, 6),
+                (3, 7),
+                (4, 1),
+                (4, 2),
+                (4, 3),
+                (4, 4),
+                (4, 5),
+                (5, 1),
+            ]
+        );
+        assert_eq!(cue.time_signature_at(1), TimeSignature::default());
+        assert_eq!(cue.time_signature_at(15).to_string(), "7/8");
+        assert_eq!(cue.time_signature_at(100).to_string(), "5/4");
+        assert_eq!(Cue::empty().time_signature_at(0), TimeSignature::default());
+    }
+
+    #[test]
+    fn group_starts() {
+        let time_signature = TimeSignature {
+            numerator: 7,
+            denominator: 8,
+            grouping: Some(vec![2, 2, 3]),
+        };
+        assert_eq!(time_signature.group_starts(), [1, 3, 5]);
+        assert_eq!(time_signature.to_string(), "7/8 (2+2+3)");
+        assert_eq!(TimeSignature::default().group_starts(), [1]);
+    }
+
+    fn ramp(curve: TempoCurve) -> Vec<usize> {
+        let mut cue = Cue::empty();
+        cue.beats = (0..6).map(|_| Beat::empty()).collect();
+        cue.beats[0]
+            .events
+            .push(BeatEvent::GradualTempoChangeEvent {
+                start_tempo: 120,
+                end_tempo: 60,
+                length: 4,
+                curve,
+            });
+        cue.recalculate_tempo_changes();
+        cue.beats.iter().map(|b| b.length).collect()
+    }
+
+    #[test]
+    fn tempo_curves() {
+        assert_eq!(
+            ramp(TempoCurve::LinearPeriod),
+            [500000, 625000, 750000, 875000, 1000000, 1000000]
+        );
+        // 120, 105, 90, 75 BPM
+        assert_eq!(
+            ramp(TempoCurve::LinearTempo),
+            [500000, 571429, 666667, 800000, 1000000, 1000000]
+        );
+        // Halving the tempo over four beats divides it by 2^(1/4) each beat.
+        let exponential = ramp(TempoCurve::Exponential);
+        assert_eq!(exponential[2], 707107);
+        assert_eq!(exponential[4], 1000000);
+        let ease_in = ramp(TempoCurve::EaseIn);
+        let ease_out = ramp(TempoCurve::EaseOut);
+        assert!(ease_in[1] < 571429 && ease_out[1] > 571429);
+        assert_eq!(ramp(TempoCurve::SCurve)[2], 666667);
+    }
+
+    #[test]
+    fn zero_tempos() {
+        let gradual = |start_tempo, end_tempo, length, curve| BeatEvent::GradualTempoChangeEvent {
+            start_tempo,
+            end_tempo,
+            length,
+            curve,
+        };
+        let mut events = vec![BeatEvent::TempoChangeEvent { tempo: 0 }];
+        for curve in [TempoCurve::LinearPeriod, TempoCurve::SCurve] {
+            events.push(gradual(0, 60, 2, curve));
+            events.push(gradual(120, 0, 2, curve));
+            events.push(gradual(120, 60, 0, curve));
+        }
+        for event in events {
+            let mut cue = Cue::example();
+            cue.beats.truncate(4);
+            cue.beats[1].events = vec![event];
+            cue.recalculate_tempo_changes();
+            assert!(
+                cue.beats.iter().all(|b| (1..=60000000).contains(&b.length)),
+                "{:?}",
+                cue.beats
+            );
+        }
+
+        // A change over no beats takes one.
+        let mut cue = Cue::example();
+        cue.beats.truncate(4);
+        cue.beats[1].events = vec![gradual(120, 60, 0, TempoCurve::LinearPeriod)];
+        cue.recalculate_tempo_changes();
+        let lengths: Vec<usize> = cue.beats.iter().map(|b| b.length).collect();
+        assert_eq!(lengths, [500000, 500000, 1000000, 1000000]);
+    }
+
+    #[test]
+    fn legacy_gradual_change() {
+        let json = r#"{"GradualTempoChangeEvent":{"start_tempo":120,"end_tempo":60,"length":4}}"#;
+        let event: BeatEvent = serde_json::from_str(json).unwrap();
+        assert!(matches!(
+            event,
+            BeatEvent::GradualTempoChangeEvent {
+                curve: TempoCurve::LinearPeriod,
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn click_patterns() {
+        let mut cue = Cue::example();
+        cue.beats[4].events.push(BeatEvent::SubdivisionEvent {
+            subdivision: Subdivision::Eighths,
+            scope: SubdivisionScope::Section,
+            accents: None,
+        });
+        cue.beats[6].events.push(BeatEvent::SubdivisionEvent {
+            subdivision: Subdivision::Triplets,
+            scope: SubdivisionScope::Beat,
+            accents: Some(vec![AccentLevel::Beat, AccentLevel::Silent]),
+        });
+        cue.beats[8].events.push(BeatEvent::SubdivisionEvent {
+            subdivision: Subdivision::None,
+            scope: SubdivisionScope::Section,
+            accents: None,
+        });
+        use AccentLevel as A;
+        assert_eq!(cue.click_pattern(0), [A::Downbeat]);
+        assert_eq!(cue.click_pattern(3), [A::Beat]);
+        assert_eq!(cue.click_pattern(4), [A::Downbeat, A::Subdivision]);
+        assert_eq!(cue.click_pattern(5), [A::Beat, A::Subdivision]);
+        assert_eq!(cue.click_pattern(6), [A::Beat, A::Silent, A::Subdivision]);
+        assert_eq!(cue.click_pattern(7), [A::Beat, A::Subdivision]);
+        assert_eq!(cue.click_pattern(8), [A::Downbeat]);
+        assert_eq!(cue.click_pattern(1000), []);
+        let patterns = cue.click_patterns();
+        assert_eq!(patterns.len(), cue.beats.len());
+        for (idx, pattern) in patterns.iter().enumerate() {
+            assert_eq!(*pattern, cue.click_pattern(idx), "beat {idx}");
+        }
+    }
+
+    #[test]
+    fn event_offsets() {
+        let mut beat = Beat {
+            length: 600000,
+            ..Beat::empty()
+        };
+        beat.events = vec![
+            BeatEvent::RehearsalMarkEvent {
+                label: "A".to_string(),
+            },
+            BeatEvent::PlaybackEvent {
+                channel_idx: 0,
+                clip_idx: 0,
+                sample: 0,
+                offset: EventOffset::Fraction {
+                    numerator: 1,
+                    denominator: 2,
+                },
+            },
+            BeatEvent::PlaybackStopEvent {
+                channel_idx: 1,
+                offset: EventOffset::Microseconds(1000000),
+            },
+        ];
+        assert_eq!(beat.event_offsets_us(), [0, 300000, 600000]);
+
+        let json = r#"{"PlaybackStopEvent":{"channel_idx":2}}"#;
+        let event: BeatEvent = serde_json::from_str(json).unwrap();
+        assert_eq!(event.offset(), EventOffset::Start);
+    }
+
+    #[test]
+    fn timecode_events() {
+        let json = r#"{"TimecodeEvent":{"timecode":"01:00:00:29"}}"#;
+        let event: BeatEvent = serde_json::from_str(json).unwrap();
+        let time = event.timecode(FrameRate::Fps30).unwrap().unwrap();
+        assert_eq!(time.to_string(), "01:00:00:29");
+        assert!(event.timecode(FrameRate::Fps25).unwrap().is_err());
+
+        let json = r#"{"TimecodeEvent":{"h":1,"m":2,"s":3,"f":4}}"#;
+        let event: BeatEvent = serde_json::from_str(json).unwrap();
+        let time = event.timecode(FrameRate::Fps25).unwrap().unwrap();
+        assert_eq!(time.to_string(), "01:02:03:04");
+        assert!(
+            BeatEvent::TempoChangeEvent { tempo: 120 }
+                .timecode(FrameRate::Fps25)
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn midi_events() {
+        let mut beat = Beat::empty();
+        beat.events = vec![
+            BeatEvent::TempoChangeEvent { tempo: 100 },
+            BeatEvent::MidiEvent {
+                output: "Keys 1".to_string(),
+                message: MidiMessage::ProgramChange {
+                    channel: 2,
+                    program: 17,
+                },
+                offset: EventOffset::Start,
+            },
+        ];
+        let midi = beat.events_filter(|e| matches!(e, BeatEvent::MidiEvent { .. }));
+        assert_eq!(midi.len(), 1);
+        assert_eq!(midi[0].get_name(), "MIDI");
+
+        let json = serde_json::to_string(&beat.events[1]).unwrap();
+        let event: BeatEvent = serde_json::from_str(&json).unwrap();
+        assert!(event == beat.events[1]);
+
+        assert_eq!(
+            MidiMessage::NoteOn {
+                channel: 9,
+                note: 36,
+                velocity: 200
+            }
+            .to_bytes(),
+            [0x99, 36, 72]
+        );
+        assert_eq!(
+            MidiMessage::ControlChange {
+                channel: 0,
+                controller: 64,
+                value: 127
+            }
+            .to_bytes(),
+            [0xB0, 64, 127]
+        );
+        assert_eq!(
+            MidiMessage::SysEx(vec![0x7E, 0x7F, 0x06, 0x01]).to_bytes(),
+            [0xF0, 0x7E, 0x7F, 0x06, 0x01, 0xF7]
+        );
+        assert_eq!(
+            MidiMessage::ProgramChange {
+                channel: 2,
+                program: 17
+            }
+            .to_string(),
+            "Program change ch 3 to 17"
+        );
+    }
+
+    #[test]
+    fn osc_events() {
+        use crate::osc::{OscArgument, OscPacket};
+
+        let event = BeatEvent::OscEvent {
+            target: "Lighting".to_string(),
+            message: OscMessage::new("/eos/cue/fire", vec![OscArgument::String("12".to_string())]),
+            offset: EventOffset::Start,
+        };
+        let json = r#"{"OscEvent":{"target":"Lighting","message":{"address":"/eos/cue/fire","arguments":[{"String":"12"}]}}}"#;
+        let parsed: BeatEvent = serde_json::from_str(json).unwrap();
+        assert!(parsed == event);
+        assert_eq!(parsed.get_name(), "OSC");
+        if let BeatEvent::OscEvent { message, .. } = parsed {
+            let packet = OscPacket::Message(message.clone()).encode();
+            assert_eq!(packet, message.encode());
+            assert_eq!(message.to_string(), r#"/eos/cue/fire "12""#);
+        }
+    }
+
+    #[test]
+    fn repeat_counters() {
+        let mut counters = RepeatCounters::new();
+        let decisions: Vec<bool> = (0..5).map(|_| counters.repeat(15, 3)).collect();
+        assert_eq!(decisions, [true, true, true, false, true]);
+        assert_eq!(counters.taken(15), 1);
+        assert_eq!(counters.taken(3), 0);
+        counters.reset();
+        assert_eq!(counters, RepeatCounters::default());
+        assert_eq!(
+            JumpRequirement::Repeat { times: 3 }.to_string(),
+            "Repeat 3x"
+        );
+    }
+}
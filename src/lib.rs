@@ -1,11 +1,21 @@
+pub mod builder;
 pub mod command;
 pub mod config;
 pub mod control;
 pub mod cue;
+pub mod lint;
+pub mod ltc;
+pub mod mtc;
 pub mod network;
+pub mod notation;
+pub mod osc;
+pub mod render;
 pub mod show;
+pub mod simulate;
+pub mod smf;
 pub mod status;
 pub mod time;
 pub mod timecode;
+pub mod timemap;
 
 pub const VERSION: &str = env!("CARGO_PKG_VERSION");
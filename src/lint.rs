@@ -0,0 +1,402 @@
+use std::fmt::{self, Display};
+
+use serde::{Deserialize, Serialize};
+
+use crate::cue::{BeatEvent, Cue, JumpRequirement, PauseEventBehaviour};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum DiagnosticKind {
+    JumpOutOfRange {
+        destination: usize,
+    },
+    PauseJumpOutOfRange {
+        destination: usize,
+    },
+    ZeroLengthBeat,
+    ConflictingTempoEvents {
+        count: usize,
+    },
+    GradualTempoChangeOverrun {
+        length: usize,
+        remaining: usize,
+    },
+    /// A tempo change, or either end of a gradual one, at 0 BPM.
+    InvalidTempo,
+    EmptyGradualTempoChange,
+    /// The cue loops back from this beat with no way of reaching its end.
+    NeverFinishes {
+        destination: usize,
+    },
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct CueDiagnostic {
+    pub beat_idx: usize,
+    pub severity: Severity,
+    pub kind: DiagnosticKind,
+}
+
+impl Display for CueDiagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Beat {}: ", self.beat_idx)?;
+        match &self.kind {
+            DiagnosticKind::JumpOutOfRange { destination } => {
+                write!(f, "Jump to beat {destination} is past the end of the cue")
+            }
+            DiagnosticKind::PauseJumpOutOfRange { destination } => {
+                write!(
+                    f,
+                    "Pause jumps to beat {destination}, past the end of the cue"
+                )
+            }
+            DiagnosticKind::ZeroLengthBeat => write!(f, "Beat has zero length"),
+            DiagnosticKind::ConflictingTempoEvents { count } => {
+                write!(f, "{count} tempo events on the same beat")
+            }
+            DiagnosticKind::GradualTempoChangeOverrun { length, remaining } => write!(
+                f,
+                "Gradual tempo change over {length} beats, but only {remaining} beats remain"
+            ),
+            DiagnosticKind::InvalidTempo => write!(f, "Tempo change to 0 BPM"),
+            DiagnosticKind::EmptyGradualTempoChange => {
+                write!(f, "Gradual tempo change over 0 beats")
+            }
+            DiagnosticKind::NeverFinishes { destination } => write!(
+                f,
+                "Jump to beat {destination} loops forever, no VLT change can leave the loop"
+            ),
+        }
+    }
+}
+
+impl CueDiagnostic {
+    fn new(beat_idx: usize, kind: DiagnosticKind) -> CueDiagnostic {
+        let severity = match kind {
+            DiagnosticKind::ConflictingTempoEvents { .. }
+            | DiagnosticKind::GradualTempoChangeOverrun { .. } => Severity::Warning,
+            _ => Severity::Error,
+        };
+        CueDiagnostic {
+            beat_idx,
+            severity,
+            kind,
+        }
+    }
+}
+
+/// Checks a cue for problems that would show up on stage, ordered by beat.
+pub fn lint(cue: &Cue) -> Vec<CueDiagnostic> {
+    let len = cue.beats.len();
+    let mut diagnostics = vec![];
+    for (idx, beat) in cue.beats.iter().enumerate() {
+        if beat.length == 0 {
+            diagnostics.push(CueDiagnostic::new(idx, DiagnosticKind::ZeroLengthBeat));
+        }
+        let tempo_events = beat
+            .events_filter(|e| {
+                matches!(
+                    e,
+                    BeatEvent::TempoChangeEvent { .. } | BeatEvent::GradualTempoChangeEvent { .. }
+                )
+            })
+            .len();
+        if tempo_events > 1 {
+            diagnostics.push(CueDiagnostic::new(
+                idx,
+                DiagnosticKind::ConflictingTempoEvents {
+                    count: tempo_events,
+                },
+            ));
+        }
+        for event in &beat.events {
+            let kind = match event {
+                BeatEvent::JumpEvent { destination, .. } if *destination >= len => {
+                    DiagnosticKind::JumpOutOfRange {
+                        destination: *destination,
+                    }
+                }
+                BeatEvent::PauseEvent {
+                    behaviour: PauseEventBehaviour::Jump { destination },
+                } if *destination >= len => DiagnosticKind::PauseJumpOutOfRange {
+                    destination: *destination,
+                },
+                BeatEvent::TempoChangeEvent { tempo: 0 }
+                | BeatEvent::GradualTempoChangeEvent { start_tempo: 0, .. }
+                | BeatEvent::GradualTempoChangeEvent { end_tempo: 0, .. } => {
+                    DiagnosticKind::InvalidTempo
+                }
+                BeatEvent::GradualTempoChangeEvent { length: 0, .. } => {
+                    DiagnosticKind::EmptyGradualTempoChange
+                }
+                BeatEvent::GradualTempoChangeEvent { length, .. } if *length > len - idx => {
+                    DiagnosticKind::GradualTempoChangeOverrun {
+                        length: *length,
+                        remaining: len - idx,
+                    }
+                }
+                _ => continue,
+            };
+            diagnostics.push(CueDiagnostic::new(idx, kind));
+        }
+    }
+    diagnostics.extend(endless_loops(cue));
+    diagnostics.sort_by_key(|d| d.beat_idx);
+    diagnostics
+}
+
+/// Beats that can follow `idx`, with `cue.beats.len()` standing for leaving the cue. The VLT can
+/// be changed by the operator at any time, so conditional jumps may go either way.
+fn successors(cue: &Cue, idx: usize) -> Vec<usize> {
+    let len = cue.beats.len();
+    let clamp = |destination: usize| destination.min(len);
+    let mut next = vec![];
+    let mut falls_through = true;
+    for event in &cue.beats[idx].events {
+        match event {
+            BeatEvent::JumpEvent {
+                destination,
+                requirement,
+                ..
+            } => {
+                next.push(clamp(*destination));
+                falls_through &= *requirement != JumpRequirement::None;
+            }
+            BeatEvent::PauseEvent { behaviour } => match behaviour {
+                PauseEventBehaviour::Hold => {}
+                PauseEventBehaviour::RestartBeat => {
+                    next.push(idx);
+                    falls_through = false;
+                }
+                PauseEventBehaviour::RestartCue => {
+                    next.push(0);
+                    falls_through = false;
+                }
+                PauseEventBehaviour::NextCue => {
+                    next.push(len);
+                    falls_through = false;
+                }
+                PauseEventBehaviour::Jump { destination } => {
+                    next.push(clamp(*destination));
+                    falls_through = false;
+                }
+            },
+            _ => {}
+        }
+    }
+    if falls_through {
+        next.push(idx + 1);
+    }
+    next
+}
+
+fn endless_loops(cue: &Cue) -> Vec<CueDiagnostic> {
+    let len = cue.beats.len();
+    if len == 0 {
+        return vec![];
+    }
+    let graph: Vec<Vec<usize>> = (0..len).map(|idx| successors(cue, idx)).collect();
+
+    let mut finishes = vec![false; len + 1];
+    finishes[len] = true;
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for idx in (0..len).rev() {
+            if !finishes[idx] && graph[idx].iter().any(|next| finishes[*next]) {
+                finishes[idx] = true;
+                changed = true;
+            }
+        }
+    }
+
+    let mut reached = vec![false; len + 1];
+    let mut stack = vec![0];
+    while let Some(idx) = stack.pop() {
+        if reached[idx] {
+            continue;
+        }
+        reached[idx] = true;
+        if idx < len {
+            stack.extend(&graph[idx]);
+        }
+    }
+
+    (0..len)
+        .filter(|idx| reached[*idx] && !finishes[*idx])
+        .filter_map(|idx| {
+            let destination = *graph[idx].iter().find(|next| **next <= idx)?;
+            Some(CueDiagnostic::new(
+                idx,
+                DiagnosticKind::NeverFinishes { destination },
+            ))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cue::{Beat, JumpModeChange, TempoCurve};
+
+    fn cue(beats: usize) -> Cue {
+        let mut cue = Cue::empty();
+        cue.beats = (0..beats)
+            .map(|_| Beat {
+                length: 500000,
+                ..Beat::empty()
+            })
+            .collect();
+        cue
+    }
+
+    fn jump(destination: usize, requirement: JumpRequirement) -> BeatEvent {
+        BeatEvent::JumpEvent {
+            destination,
+            requirement,
+            when_jumped: JumpModeChange::None,
+            when_passed: JumpModeChange::None,
+        }
+    }
+
+    fn kinds(cue: &Cue) -> Vec<(usize, DiagnosticKind)> {
+        lint(cue)
+            .into_iter()
+            .map(|d| (d.beat_idx, d.kind))
+            .collect()
+    }
+
+    #[test]
+    fn clean_cue() {
+        let mut cue = cue(16);
+        cue.beats[7]
+            .events
+            .push(jump(4, JumpRequirement::JumpModeOn));
+        cue.beats[0]
+            .events
+            .push(BeatEvent::TempoChangeEvent { tempo: 120 });
+        assert_eq!(lint(&cue), []);
+    }
+
+    #[test]
+    fn structural_problems() {
+        let mut cue = cue(8);
+        cue.beats[1].length = 0;
+        cue.beats[2].events.push(jump(8, JumpRequirement::None));
+        cue.beats[3].events.push(BeatEvent::PauseEvent {
+            behaviour: PauseEventBehaviour::Jump { destination: 20 },
+        });
+        cue.beats[4]
+            .events
+            .push(BeatEvent::TempoChangeEvent { tempo: 100 });
+        cue.beats[4]
+            .events
+            .push(BeatEvent::GradualTempoChangeEvent {
+                start_tempo: 100,
+                end_tempo: 80,
+                length: 6,
+                curve: TempoCurve::LinearTempo,
+            });
+        assert_eq!(
+            kinds(&cue),
+            [
+                (1, DiagnosticKind::ZeroLengthBeat),
+                (2, DiagnosticKind::JumpOutOfRange { destination: 8 }),
+                (3, DiagnosticKind::PauseJumpOutOfRange { destination: 20 }),
+                (4, DiagnosticKind::ConflictingTempoEvents { count: 2 }),
+                (
+                    4,
+                    DiagnosticKind::GradualTempoChangeOverrun {
+                        length: 6,
+                        remaining: 4
+                    }
+                ),
+            ]
+        );
+        assert_eq!(lint(&cue)[0].severity, Severity::Error);
+        assert_eq!(lint(&cue)[3].severity, Severity::Warning);
+    }
+
+    #[test]
+    fn invalid_tempos() {
+        let gradual = |start_tempo, end_tempo, length| BeatEvent::GradualTempoChangeEvent {
+            start_tempo,
+            end_tempo,
+            length,
+            curve: TempoCurve::LinearPeriod,
+        };
+        let mut cue = cue(8);
+        cue.beats[0]
+            .events
+            .push(BeatEvent::TempoChangeEvent { tempo: 0 });
+        cue.beats[1].events.push(gradual(0, 60, 2));
+        cue.beats[3].events.push(gradual(120, 0, 2));
+        cue.beats[5].events.push(gradual(120, 60, 0));
+        assert_eq!(
+            kinds(&cue),
+            [
+                (0, DiagnosticKind::InvalidTempo),
+                (1, DiagnosticKind::InvalidTempo),
+                (3, DiagnosticKind::InvalidTempo),
+                (5, DiagnosticKind::EmptyGradualTempoChange),
+            ]
+        );
+        assert!(lint(&cue).iter().all(|d| d.severity == Severity::Error));
+        assert_eq!(lint(&cue)[0].to_string(), "Beat 0: Tempo change to 0 BPM");
+    }
+
+    #[test]
+    fn endless_loop() {
+        // Leaving the vamp leads to a pause that restarts the cue, and so back into the vamp.
+        let mut cue = cue(12);
+        cue.beats[7]
+            .events
+            .push(jump(4, JumpRequirement::JumpModeOn));
+        cue.beats[9].events.push(BeatEvent::PauseEvent {
+            behaviour: PauseEventBehaviour::RestartCue,
+        });
+        assert_eq!(
+            kinds(&cue),
+            [
+                (7, DiagnosticKind::NeverFinishes { destination: 4 }),
+                (9, DiagnosticKind::NeverFinishes { destination: 0 }),
+            ]
+        );
+
+        let cue = Cue::example_loop();
+        assert_eq!(
+            kinds(&cue),
+            [(3, DiagnosticKind::NeverFinishes { destination: 0 })]
+        );
+        assert_eq!(
+            lint(&cue)[0].to_string(),
+            "Beat 3: Jump to beat 0 loops forever, no VLT change can leave the loop"
+        );
+    }
+
+    #[test]
+    fn loop_with_exit() {
+        let mut cue = cue(12);
+        cue.beats[7].events.push(jump(4, JumpRequirement::None));
+        assert_eq!(
+            kinds(&cue),
+            [(7, DiagnosticKind::NeverFinishes { destination: 4 })]
+        );
+        // A VLT-dependent jump out of the loop lets it finish.
+        cue.beats[5]
+            .events
+            .push(jump(10, JumpRequirement::JumpModeOff));
+        assert_eq!(kinds(&cue), []);
+        // So does a pause that moves on to the next cue.
+        cue.beats[5].events.clear();
+        cue.beats[6].events.push(BeatEvent::PauseEvent {
+            behaviour: PauseEventBehaviour::NextCue,
+        });
+        assert_eq!(kinds(&cue), []);
+    }
+}
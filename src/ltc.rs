@@ -0,0 +1,599 @@
+use crate::timecode::{FrameRate, TimecodeDirection, TimecodeInstant, UserBits};
+
+pub const LTC_BITS_PER_FRAME: usize = 80;
+
+/// Bits 64..=79 of every frame, least significant bit first.
+const SYNC_WORD: u16 = 0xBFFC;
+
+/// One 80-bit SMPTE linear timecode frame, stored least significant bit first in transmission
+/// order.
+///
+/// Rates above 30 fps are carried as their half rate with the frame number halved, as LTC has
+/// only two bits for frame tens.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct LtcFrame {
+    pub bytes: [u8; 10],
+    /// Decides where the flags and polarity bit sit, which differs for 25 fps.
+    pub frame_rate: FrameRate,
+}
+
+impl LtcFrame {
+    pub fn new(time: &TimecodeInstant) -> LtcFrame {
+        let mut frame = LtcFrame {
+            bytes: [0; 10],
+            frame_rate: time.frame_rate,
+        };
+        let frame_number = if time.frame_rate.nominal() > 30 {
+            time.f / 2
+        } else {
+            time.f
+        } as u32;
+        frame.set_bcd(0, 8, 2, frame_number);
+        frame.set_bcd(16, 24, 3, time.s as u32);
+        frame.set_bcd(32, 40, 3, time.m as u32);
+        frame.set_bcd(48, 56, 2, time.h as u32);
+        for group in 0..8 {
+            frame.set_bits(group * 8 + 4, 4, time.user_bits.bits >> (group * 4));
+        }
+        frame.set_bit(10, time.frame_rate.is_drop_frame());
+        frame.set_bits(64, 16, SYNC_WORD as u32);
+        frame.set_binary_group_flags(time.user_bits.binary_group_flags());
+        frame
+    }
+
+    pub fn bit(&self, idx: usize) -> bool {
+        self.bytes[idx / 8] & (1 << (idx % 8)) != 0
+    }
+
+    pub fn user_bits(&self) -> u32 {
+        (0..8).fold(0, |bits, group| {
+            bits | (self.get_bits(group * 8 + 4, 4) << (group * 4))
+        })
+    }
+
+    /// Timecode carried by the frame. Reading is the reverse of `new`, including the halved frame
+    /// number at rates above 30 fps.
+    pub fn timecode(&self) -> TimecodeInstant {
+        let frame_number = self.get_bcd(0, 8, 2);
+        let mut time = TimecodeInstant::new(self.frame_rate);
+        time.set_time(
+            self.get_bcd(48, 56, 2) as usize,
+            self.get_bcd(32, 40, 3) as usize,
+            self.get_bcd(16, 24, 3) as usize,
+            if self.frame_rate.nominal() > 30 {
+                frame_number * 2
+            } else {
+                frame_number
+            } as usize,
+        );
+        time.user_bits =
+            UserBits::from_binary_group_flags(self.user_bits(), self.binary_group_flags());
+        time
+    }
+
+    pub fn drop_frame(&self) -> bool {
+        self.bit(10)
+    }
+
+    pub fn color_frame(&self) -> bool {
+        self.bit(11)
+    }
+
+    pub fn set_color_frame(&mut self, color_frame: bool) {
+        self.set_bit(11, color_frame);
+        self.update_parity();
+    }
+
+    /// Binary group flags as `BGF2 BGF1 BGF0` in the three low bits.
+    pub fn binary_group_flags(&self) -> u8 {
+        let [bgf0, bgf2] = self.binary_group_flag_positions();
+        self.bit(bgf0) as u8 | (self.bit(58) as u8) << 1 | (self.bit(bgf2) as u8) << 2
+    }
+
+    pub fn set_binary_group_flags(&mut self, flags: u8) {
+        let [bgf0, bgf2] = self.binary_group_flag_positions();
+        self.set_bit(bgf0, flags & 1 != 0);
+        self.set_bit(58, flags & 2 != 0);
+        self.set_bit(bgf2, flags & 4 != 0);
+        self.update_parity();
+    }
+
+    fn binary_group_flag_positions(&self) -> [usize; 2] {
+        if self.is_25_fps() { [27, 43] } else { [43, 59] }
+    }
+
+    fn is_25_fps(&self) -> bool {
+        ltc_frame_rate(self.frame_rate).nominal() == 25
+    }
+
+    fn parity_position(&self) -> usize {
+        if self.is_25_fps() { 59 } else { 27 }
+    }
+
+    /// Sets the polarity correction bit so that every frame carries an even number of ones.
+    fn update_parity(&mut self) {
+        let parity = self.parity_position();
+        self.set_bit(parity, false);
+        let ones: u32 = self.bytes.iter().map(|b| b.count_ones()).sum();
+        self.set_bit(parity, ones % 2 == 1);
+    }
+
+    fn set_bit(&mut self, idx: usize, value: bool) {
+        if value {
+            self.bytes[idx / 8] |= 1 << (idx % 8);
+        } else {
+            self.bytes[idx / 8] &= !(1 << (idx % 8));
+        }
+    }
+
+    fn set_bits(&mut self, start: usize, len: usize, value: u32) {
+        for i in 0..len {
+            self.set_bit(start + i, value & (1 << i) != 0);
+        }
+    }
+
+    fn get_bits(&self, start: usize, len: usize) -> u32 {
+        (0..len).fold(0, |value, i| value | (self.bit(start + i) as u32) << i)
+    }
+
+    fn set_bcd(&mut self, units: usize, tens: usize, tens_len: usize, value: u32) {
+        self.set_bits(units, 4, value % 10);
+        self.set_bits(tens, tens_len, value / 10);
+    }
+
+    fn get_bcd(&self, units: usize, tens: usize, tens_len: usize) -> u32 {
+        self.get_bits(units, 4) + self.get_bits(tens, tens_len) * 10
+    }
+
+    fn from_bits(bits: u128, frame_rate: FrameRate) -> LtcFrame {
+        LtcFrame {
+            bytes: std::array::from_fn(|i| (bits >> (i * 8)) as u8),
+            frame_rate,
+        }
+    }
+}
+
+/// Renders LTC frames as biphase-mark audio. Bit edges are placed from a running bit count so
+/// that fractional samples per bit do not accumulate drift.
+#[derive(Clone, Debug)]
+pub struct LtcEncoder {
+    pub sample_rate: usize,
+    pub frame_rate: FrameRate,
+    pub level: f32,
+    half_bits: u64,
+    high: bool,
+}
+
+impl LtcEncoder {
+    pub fn new(sample_rate: usize, frame_rate: FrameRate, level: f32) -> LtcEncoder {
+        LtcEncoder {
+            sample_rate,
+            frame_rate,
+            level,
+            half_bits: 0,
+            high: false,
+        }
+    }
+
+    pub fn encode(&mut self, frame: &LtcFrame) -> Vec<f32> {
+        let mut samples = vec![];
+        self.encode_into(frame, &mut samples);
+        samples
+    }
+
+    pub fn encode_into(&mut self, frame: &LtcFrame, samples: &mut Vec<f32>) {
+        for idx in 0..LTC_BITS_PER_FRAME {
+            for half in 0..2 {
+                if half == 0 || frame.bit(idx) {
+                    self.high = !self.high;
+                }
+                let len =
+                    self.half_bit_edge(self.half_bits + 1) - self.half_bit_edge(self.half_bits);
+                let value = if self.high { self.level } else { -self.level };
+                samples.extend(std::iter::repeat_n(value, len as usize));
+                self.half_bits += 1;
+            }
+        }
+    }
+
+    /// Sample index at which the given half bit starts.
+    fn half_bit_edge(&self, half_bit: u64) -> u64 {
+        let (num, den) = ltc_frame_rate(self.frame_rate).frames_per_second();
+        let per_second = num * LTC_BITS_PER_FRAME as u64 * 2;
+        (half_bit * self.sample_rate as u64 * den + per_second / 2) / per_second
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct LtcDecoded {
+    pub frame: LtcFrame,
+    /// Position at the end of the buffer passed to `LtcDecoder::decode`, with `frame_progress`
+    /// holding how far into the frame the buffer ended.
+    pub time: TimecodeInstant,
+    pub direction: TimecodeDirection,
+    /// Index into the buffer where the last bit of the frame ended.
+    pub sample_idx: usize,
+}
+
+/// Rates a decoder can tell apart on the wire.
+const LTC_FRAME_RATES: [FrameRate; 5] = [
+    FrameRate::Fps23_976,
+    FrameRate::Fps24,
+    FrameRate::Fps25,
+    FrameRate::Fps29_97,
+    FrameRate::Fps30,
+];
+
+/// Streaming biphase-mark decoder. Feed it audio in buffers of any size; frames are reported as
+/// soon as their sync word has been read in either direction.
+#[derive(Clone, Debug)]
+pub struct LtcDecoder {
+    pub sample_rate: usize,
+    /// Detected rate, which settles after a few frames.
+    pub frame_rate: FrameRate,
+    peak: f32,
+    high: bool,
+    samples_since_edge: usize,
+    /// Running estimate of samples per bit.
+    bit_period: f32,
+    half_bit_pending: bool,
+    bits: u128,
+    bits_since_frame: usize,
+    samples_total: u64,
+    last_frame_end: Option<u64>,
+    /// Running estimate of samples per frame, once two consecutive frames have been seen.
+    frame_period: Option<f64>,
+    max_frame_number: i16,
+}
+
+impl LtcDecoder {
+    pub fn new(sample_rate: usize) -> LtcDecoder {
+        LtcDecoder {
+            sample_rate,
+            frame_rate: FrameRate::Fps25,
+            peak: 0.0,
+            high: false,
+            samples_since_edge: 0,
+            bit_period: sample_rate as f32 / (25 * LTC_BITS_PER_FRAME) as f32,
+            half_bit_pending: false,
+            bits: 0,
+            bits_since_frame: 0,
+            samples_total: 0,
+            last_frame_end: None,
+            frame_period: None,
+            max_frame_number: 0,
+        }
+    }
+
+    pub fn decode(&mut self, samples: &[f32]) -> Vec<LtcDecoded> {
+        let mut decoded = vec![];
+        for (idx, sample) in samples.iter().enumerate() {
+            self.peak = sample.abs().max(self.peak * 0.999);
+            let threshold = self.peak * 0.2;
+            self.samples_since_edge += 1;
+            self.samples_total += 1;
+            if (!self.high && *sample > threshold) || (self.high && *sample < -threshold) {
+                self.high = !self.high;
+                let interval = self.samples_since_edge;
+                self.samples_since_edge = 0;
+                self.read_interval(interval, idx, &mut decoded);
+            } else if self.samples_since_edge as f32 > self.bit_period * 4.0 {
+                self.reset_bits();
+            }
+        }
+        for frame in &mut decoded {
+            self.set_position(frame, samples.len());
+        }
+        decoded
+    }
+
+    /// Called when the signal drops out. The source may come back at a different rate, so the
+    /// frame rate is detected afresh.
+    fn reset_bits(&mut self) {
+        self.half_bit_pending = false;
+        self.bits_since_frame = 0;
+        self.last_frame_end = None;
+        self.frame_period = None;
+        self.max_frame_number = 0;
+    }
+
+    fn read_interval(&mut self, interval: usize, idx: usize, decoded: &mut Vec<LtcDecoded>) {
+        let interval = interval as f32;
+        if interval > self.bit_period * 0.75 {
+            self.bit_period = self.bit_period * 0.9 + interval * 0.1;
+            self.half_bit_pending = false;
+            self.read_bit(false, idx, decoded);
+        } else if self.half_bit_pending {
+            self.bit_period = self.bit_period * 0.9 + interval * 0.2;
+            self.half_bit_pending = false;
+            self.read_bit(true, idx, decoded);
+        } else {
+            self.bit_period = self.bit_period * 0.9 + interval * 0.2;
+            self.half_bit_pending = true;
+        }
+    }
+
+    fn read_bit(&mut self, bit: bool, idx: usize, decoded: &mut Vec<LtcDecoded>) {
+        self.bits = (self.bits >> 1) | (bit as u128) << (LTC_BITS_PER_FRAME - 1);
+        self.bits_since_frame += 1;
+        if self.bits_since_frame < LTC_BITS_PER_FRAME {
+            return;
+        }
+        let reversed = self.bits.reverse_bits() >> (128 - LTC_BITS_PER_FRAME);
+        let (bits, direction) = if (self.bits >> 64) as u16 == SYNC_WORD {
+            (self.bits, TimecodeDirection::Forward)
+        } else if (reversed >> 64) as u16 == SYNC_WORD {
+            (reversed, TimecodeDirection::Reverse)
+        } else {
+            return;
+        };
+        self.bits_since_frame = 0;
+        self.measure_frame_period();
+        let frame = LtcFrame::from_bits(bits, FrameRate::Fps25);
+        self.max_frame_number = self.max_frame_number.max(frame.timecode().f);
+        self.detect_frame_rate(frame.drop_frame());
+        let frame = LtcFrame::from_bits(bits, self.frame_rate);
+        decoded.push(LtcDecoded {
+            time: frame.timecode(),
+            frame,
+            direction,
+            sample_idx: idx,
+        });
+    }
+
+    fn measure_frame_period(&mut self) {
+        let frame_end = self.samples_total;
+        if let Some(last) = self.last_frame_end {
+            let period = (frame_end - last) as f64;
+            let expected = self.bit_period as f64 * LTC_BITS_PER_FRAME as f64;
+            if (period - expected).abs() < expected * 0.2 {
+                self.frame_period = Some(match self.frame_period {
+                    Some(average) => average * 0.8 + period * 0.2,
+                    None => period,
+                });
+            }
+        }
+        self.last_frame_end = Some(frame_end);
+    }
+
+    fn detect_frame_rate(&mut self, drop_frame: bool) {
+        if drop_frame {
+            self.frame_rate = FrameRate::Fps29_97Df;
+            return;
+        }
+        let period = self
+            .frame_period
+            .unwrap_or(self.bit_period as f64 * LTC_BITS_PER_FRAME as f64);
+        let measured = self.sample_rate as f64 / period;
+        if let Some(rate) = LTC_FRAME_RATES
+            .into_iter()
+            .filter(|rate| rate.nominal() as i16 > self.max_frame_number)
+            .min_by(|a, b| {
+                (a.as_f64() - measured)
+                    .abs()
+                    .total_cmp(&(b.as_f64() - measured).abs())
+            })
+        {
+            self.frame_rate = rate;
+        }
+    }
+
+    /// Moves a decoded frame's time from the frame it carries to the end of the buffer.
+    fn set_position(&self, decoded: &mut LtcDecoded, buffer_len: usize) {
+        let period = self
+            .frame_period
+            .unwrap_or(self.bit_period as f64 * LTC_BITS_PER_FRAME as f64);
+        let (num, den) = self.frame_rate.frame_duration();
+        let frame_us = 1000000.0 * num as f64 / den as f64;
+        let since_us = ((buffer_len - decoded.sample_idx) as f64 / period * frame_us) as u64;
+        match decoded.direction {
+            TimecodeDirection::Forward => {
+                decoded.time.add_progress(u16::MAX);
+                decoded.time.add_progress(1);
+                decoded.time.add_us(since_us);
+            }
+            TimecodeDirection::Reverse => decoded.time.sub_us(since_us),
+        }
+    }
+}
+
+/// Rate at which LTC frames are sent for a given timecode rate.
+pub fn ltc_frame_rate(frame_rate: FrameRate) -> FrameRate {
+    match frame_rate {
+        FrameRate::Fps47_95 => FrameRate::Fps23_976,
+        FrameRate::Fps48 => FrameRate::Fps24,
+        FrameRate::Fps50 => FrameRate::Fps25,
+        FrameRate::Fps59_94 => FrameRate::Fps29_97,
+        FrameRate::Fps59_94Df => FrameRate::Fps29_97Df,
+        FrameRate::Fps60 => FrameRate::Fps30,
+        rate => rate,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::timecode::UserBitsDate;
+
+    fn time(frame_rate: FrameRate, h: usize, m: usize, s: usize, f: usize) -> TimecodeInstant {
+        let mut time = TimecodeInstant::new(frame_rate);
+        time.set_time(h, m, s, f);
+        time
+    }
+
+    #[test]
+    fn golden_frames() {
+        let frame = LtcFrame::new(&time(FrameRate::Fps25, 0, 0, 0, 0));
+        assert_eq!(frame.bytes, [0, 0, 0, 0, 0, 0, 0, 0x08, 0xFC, 0xBF]);
+
+        let frame = LtcFrame::new(&time(FrameRate::Fps30, 1, 23, 45, 12));
+        assert_eq!(
+            frame.bytes,
+            [0x02, 0x01, 0x05, 0x04, 0x03, 0x02, 0x01, 0x00, 0xFC, 0xBF]
+        );
+
+        let mut tc = time(FrameRate::Fps29_97Df, 0, 1, 0, 2);
+        tc.user_bits = UserBits::raw(0x87654321);
+        let frame = LtcFrame::new(&tc);
+        assert_eq!(
+            frame.bytes,
+            [0x12, 0x24, 0x30, 0x48, 0x51, 0x60, 0x70, 0x80, 0xFC, 0xBF]
+        );
+        assert_eq!(frame.user_bits(), 0x87654321);
+        assert!(frame.drop_frame());
+
+        // Date in user bits, flagged with BGF2, which sits at bit 59 outside 25 fps.
+        let mut tc = time(FrameRate::Fps30, 0, 0, 0, 0);
+        tc.user_bits = UserBits::from_date(UserBitsDate {
+            year: 26,
+            month: 10,
+            day: 18,
+            time_zone: 0,
+        });
+        let frame = LtcFrame::new(&tc);
+        assert_eq!(
+            frame.bytes,
+            [0x80, 0x10, 0x00, 0x10, 0x60, 0x20, 0x00, 0x08, 0xFC, 0xBF]
+        );
+        assert_eq!(frame.timecode().user_bits, tc.user_bits);
+    }
+
+    #[test]
+    fn flags_keep_parity() {
+        for rate in [FrameRate::Fps25, FrameRate::Fps30] {
+            let mut tc = time(rate, 10, 20, 30, 4);
+            tc.user_bits = UserBits::raw(0xDEADBEEF);
+            let mut frame = LtcFrame::new(&tc);
+            for flags in 0..8 {
+                frame.set_binary_group_flags(flags);
+                frame.set_color_frame(flags % 2 == 0);
+                assert_eq!(frame.binary_group_flags(), flags);
+                assert_eq!(frame.user_bits(), 0xDEADBEEF);
+                let ones: u32 = frame.bytes.iter().map(|b| b.count_ones()).sum();
+                assert_eq!(ones % 2, 0);
+            }
+        }
+    }
+
+    #[test]
+    fn biphase_mark() {
+        let frame = LtcFrame::new(&time(FrameRate::Fps25, 0, 0, 0, 0));
+        let mut encoder = LtcEncoder::new(48000, FrameRate::Fps25, 0.5);
+        let samples = encoder.encode(&frame);
+        assert_eq!(samples.len(), 1920);
+        let transitions = samples.windows(2).filter(|w| w[0] != w[1]).count();
+        // One transition per bit plus one per set bit, minus the edge at sample 0.
+        assert_eq!(transitions, 80 + 14 - 1);
+        assert!(samples[..24].iter().all(|s| *s == 0.5));
+        assert!(samples[24..48].iter().all(|s| *s == -0.5));
+    }
+
+    #[test]
+    fn fractional_samples_per_frame() {
+        let frame = LtcFrame::new(&time(FrameRate::Fps29_97Df, 0, 0, 0, 0));
+        let mut encoder = LtcEncoder::new(48000, FrameRate::Fps29_97Df, 1.0);
+        let len: usize = (0..5).map(|_| encoder.encode(&frame).len()).sum();
+        assert_eq!(len, 8008);
+        let mut encoder = LtcEncoder::new(44100, FrameRate::Fps59_94Df, 1.0);
+        let len: usize = (0..30000).map(|_| encoder.encode(&frame).len()).sum();
+        assert_eq!(len, 44100 * 1001);
+    }
+
+    fn encode_run(sample_rate: usize, frame_rate: FrameRate, frames: usize) -> Vec<f32> {
+        let mut encoder = LtcEncoder::new(sample_rate, frame_rate, 0.3);
+        let mut time = time(frame_rate, 0, 59, 59, 10);
+        time.user_bits = UserBits::raw(0x12345678);
+        let mut samples = vec![];
+        for _ in 0..frames {
+            encoder.encode_into(&LtcFrame::new(&time), &mut samples);
+            time.add_progress(u16::MAX);
+            time.add_progress(1);
+        }
+        samples
+    }
+
+    #[test]
+    fn decode_forward() {
+        let samples = encode_run(48000, FrameRate::Fps25, 40);
+        let mut decoder = LtcDecoder::new(48000);
+        let decoded: Vec<LtcDecoded> = samples
+            .chunks(256)
+            .flat_map(|chunk| decoder.decode(chunk))
+            .collect();
+        // The first frame has no preceding edge to end its last bit on.
+        assert_eq!(decoded.len(), 39);
+        let mut expected = time(FrameRate::Fps25, 0, 59, 59, 10);
+        for frame in &decoded {
+            assert_eq!(frame.direction, TimecodeDirection::Forward);
+            assert_eq!(frame.frame.timecode(), expected);
+            assert_eq!(frame.frame.user_bits(), 0x12345678);
+            expected.add_progress(u16::MAX);
+            expected.add_progress(1);
+        }
+        assert_eq!(decoder.frame_rate, FrameRate::Fps25);
+        assert_eq!(decoded[14].time.to_string(), "01:00:00:00");
+    }
+
+    #[test]
+    fn decode_position_within_frame() {
+        let samples = encode_run(48000, FrameRate::Fps25, 3);
+        let mut decoder = LtcDecoder::new(48000);
+        decoder.decode(&samples[..1920]);
+        let decoded = decoder.decode(&samples[1920..1920 + 960]);
+        assert_eq!(decoded.len(), 1);
+        assert_eq!(decoded[0].time.to_string(), "00:59:59:11");
+        assert!((decoded[0].time.frame_progress as i32 - 32768).abs() < 200);
+    }
+
+    #[test]
+    fn decode_reverse() {
+        let mut samples = encode_run(48000, FrameRate::Fps30, 20);
+        samples.reverse();
+        let mut decoder = LtcDecoder::new(48000);
+        let decoded = decoder.decode(&samples);
+        assert_eq!(decoded.len(), 19);
+        let mut expected = time(FrameRate::Fps30, 0, 59, 59, 29);
+        for frame in &decoded {
+            assert_eq!(frame.direction, TimecodeDirection::Reverse);
+            assert_eq!(frame.frame.timecode(), expected);
+            expected.f -= 1;
+        }
+        assert_eq!(decoder.frame_rate, FrameRate::Fps30);
+    }
+
+    #[test]
+    fn detect_frame_rate() {
+        for frame_rate in [
+            FrameRate::Fps23_976,
+            FrameRate::Fps24,
+            FrameRate::Fps25,
+            FrameRate::Fps29_97,
+            FrameRate::Fps29_97Df,
+            FrameRate::Fps30,
+        ] {
+            for sample_rate in [44100, 48000, 96000] {
+                let samples = encode_run(sample_rate, frame_rate, 50);
+                let mut decoder = LtcDecoder::new(sample_rate);
+                let decoded = decoder.decode(&samples);
+                assert_eq!(decoded.len(), 49, "{frame_rate} at {sample_rate}");
+                assert_eq!(
+                    decoder.frame_rate, frame_rate,
+                    "{frame_rate} at {sample_rate}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn detect_frame_rate_change() {
+        let mut decoder = LtcDecoder::new(48000);
+        decoder.decode(&encode_run(48000, FrameRate::Fps30, 50));
+        assert_eq!(decoder.frame_rate, FrameRate::Fps30);
+        decoder.decode(&[0.0; 4800]);
+        let decoded = decoder.decode(&encode_run(48000, FrameRate::Fps25, 200));
+        // The bit period takes a frame or two to settle on the new rate.
+        assert!(decoded.len() >= 197);
+        assert_eq!(decoder.frame_rate, FrameRate::Fps25);
+        assert_eq!(decoded.last().unwrap().time.to_string(), "01:00:07:10");
+    }
+}
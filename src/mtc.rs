@@ -0,0 +1,334 @@
+use std::{error::Error, fmt};
+
+use crate::timecode::{FrameRate, TimecodeDirection, TimecodeInstant, UserBits};
+
+pub const QUARTER_FRAME: u8 = 0xF1;
+pub const SYSEX_START: u8 = 0xF0;
+pub const SYSEX_END: u8 = 0xF7;
+/// Device id addressing every receiver.
+pub const ALL_DEVICES: u8 = 0x7F;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum MtcError {
+    UnsupportedFrameRate(FrameRate),
+    InvalidMessage,
+}
+
+impl fmt::Display for MtcError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MtcError::UnsupportedFrameRate(rate) => write!(f, "MTC cannot carry {rate} fps"),
+            MtcError::InvalidMessage => write!(f, "Not an MTC message"),
+        }
+    }
+}
+
+impl Error for MtcError {}
+
+/// MTC rate code. 23.976 and 29.97 non-drop have no code of their own and are sent as 24 and 30.
+fn rate_code(frame_rate: FrameRate) -> Result<u8, MtcError> {
+    match frame_rate {
+        FrameRate::Fps23_976 | FrameRate::Fps24 => Ok(0),
+        FrameRate::Fps25 => Ok(1),
+        FrameRate::Fps29_97Df => Ok(2),
+        FrameRate::Fps29_97 | FrameRate::Fps30 => Ok(3),
+        rate => Err(MtcError::UnsupportedFrameRate(rate)),
+    }
+}
+
+fn rate_from_code(code: u8) -> FrameRate {
+    match code & 3 {
+        0 => FrameRate::Fps24,
+        1 => FrameRate::Fps25,
+        2 => FrameRate::Fps29_97Df,
+        _ => FrameRate::Fps30,
+    }
+}
+
+/// The eight quarter-frame messages describing `time`, pieces 0 to 7. Sent forward, the receiver
+/// completes them two frames after piece 0.
+pub fn quarter_frames(time: &TimecodeInstant) -> Result<[[u8; 2]; 8], MtcError> {
+    let rate = rate_code(time.frame_rate)?;
+    let nibbles = [
+        time.f & 0xF,
+        (time.f >> 4) & 0x1,
+        time.s & 0xF,
+        (time.s >> 4) & 0x3,
+        time.m & 0xF,
+        (time.m >> 4) & 0x3,
+        time.h & 0xF,
+        (time.h >> 4) & 0x1 | (rate as i16) << 1,
+    ];
+    Ok(std::array::from_fn(|piece| {
+        [QUARTER_FRAME, (piece as u8) << 4 | nibbles[piece] as u8]
+    }))
+}
+
+/// Full-frame SysEx message, sent when locating rather than running.
+pub fn full_frame(time: &TimecodeInstant, device_id: u8) -> Result<Vec<u8>, MtcError> {
+    let rate = rate_code(time.frame_rate)?;
+    Ok(vec![
+        SYSEX_START,
+        0x7F,
+        device_id,
+        0x01,
+        0x01,
+        rate << 5 | (time.h as u8 & 0x1F),
+        time.m as u8,
+        time.s as u8,
+        time.f as u8,
+        SYSEX_END,
+    ])
+}
+
+pub fn parse_full_frame(message: &[u8]) -> Result<TimecodeInstant, MtcError> {
+    match message {
+        [SYSEX_START, 0x7F, _, 0x01, 0x01, hr, m, s, f, SYSEX_END] => {
+            let mut time = TimecodeInstant::new(rate_from_code(hr >> 5));
+            time.set_time((hr & 0x1F) as usize, *m as usize, *s as usize, *f as usize);
+            Ok(time)
+        }
+        _ => Err(MtcError::InvalidMessage),
+    }
+}
+
+/// User bits SysEx message. The ninth nibble carries BGF0 in bit 0 and BGF2 in bit 1, the two flags
+/// describing the format of the bits.
+pub fn user_bits_message(user_bits: &UserBits, device_id: u8) -> Vec<u8> {
+    let flags = user_bits.binary_group_flags();
+    let mut message = vec![SYSEX_START, 0x7F, device_id, 0x01, 0x02];
+    message.extend((1..=8).map(|group| user_bits.group(group)));
+    message.push(flags & 1 | (flags >> 1) & 2);
+    message.push(SYSEX_END);
+    message
+}
+
+pub fn parse_user_bits(message: &[u8]) -> Result<UserBits, MtcError> {
+    match message {
+        [
+            SYSEX_START,
+            0x7F,
+            _,
+            0x01,
+            0x02,
+            groups @ ..,
+            flags,
+            SYSEX_END,
+        ] if groups.len() == 8 => {
+            let bits = groups
+                .iter()
+                .rev()
+                .fold(0, |bits, group| bits << 4 | (group & 0xF) as u32);
+            Ok(UserBits::from_binary_group_flags(
+                bits,
+                flags & 1 | (flags & 2) << 1,
+            ))
+        }
+        _ => Err(MtcError::InvalidMessage),
+    }
+}
+
+/// Collects quarter-frame messages into complete timecode. A value is produced each time all
+/// eight pieces have arrived in order, compensated for the two frames the cycle took to send.
+#[derive(Clone, Debug, Default)]
+pub struct MtcReassembler {
+    pub direction: TimecodeDirection,
+    /// Last received user bits, attached to every timecode produced after them.
+    pub user_bits: UserBits,
+    nibbles: [u8; 8],
+    received: u8,
+    last_piece: Option<u8>,
+}
+
+impl MtcReassembler {
+    pub fn new() -> MtcReassembler {
+        MtcReassembler::default()
+    }
+
+    /// Accepts a quarter-frame, full-frame or user bits message, returning the timecode it
+    /// completes.
+    pub fn push(&mut self, message: &[u8]) -> Option<TimecodeInstant> {
+        match message {
+            [QUARTER_FRAME, data] => self.push_quarter_frame(*data),
+            [SYSEX_START, ..] => {
+                if let Ok(user_bits) = parse_user_bits(message) {
+                    self.user_bits = user_bits;
+                    return None;
+                }
+                let mut time = parse_full_frame(message).ok()?;
+                time.user_bits = self.user_bits;
+                self.reset();
+                Some(time)
+            }
+            _ => None,
+        }
+    }
+
+    pub fn push_quarter_frame(&mut self, data: u8) -> Option<TimecodeInstant> {
+        let piece = data >> 4 & 0x7;
+        match self.last_piece {
+            Some(last) if piece == (last + 1) % 8 || piece == (last + 7) % 8 => {
+                let direction = if piece == (last + 1) % 8 {
+                    TimecodeDirection::Forward
+                } else {
+                    TimecodeDirection::Reverse
+                };
+                if direction != self.direction {
+                    self.received &= 1 << last;
+                    self.direction = direction;
+                }
+            }
+            _ => self.received = 0,
+        }
+        self.last_piece = Some(piece);
+        let first = match self.direction {
+            TimecodeDirection::Forward => 0,
+            TimecodeDirection::Reverse => 7,
+        };
+        if piece == first {
+            self.received = 0;
+        }
+        self.nibbles[piece as usize] = data & 0xF;
+        self.received |= 1 << piece;
+        if self.received != 0xFF || piece != 7 - first {
+            return None;
+        }
+        self.received = 0;
+        Some(self.assemble())
+    }
+
+    fn reset(&mut self) {
+        self.received = 0;
+        self.last_piece = None;
+    }
+
+    fn assemble(&self) -> TimecodeInstant {
+        let n = self.nibbles.map(|n| n as usize);
+        let mut time = TimecodeInstant::new(rate_from_code((n[7] >> 1) as u8));
+        time.set_time(
+            n[6] | (n[7] & 1) << 4,
+            n[4] | n[5] << 4,
+            n[2] | n[3] << 4,
+            n[0] | n[1] << 4,
+        );
+        time.user_bits = self.user_bits;
+        match self.direction {
+            TimecodeDirection::Forward => time.set_frame_count(time.frame_count() + 2),
+            TimecodeDirection::Reverse => time.set_frame_count(time.frame_count() - 2),
+        }
+        time
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn time(frame_rate: FrameRate, h: usize, m: usize, s: usize, f: usize) -> TimecodeInstant {
+        let mut time = TimecodeInstant::new(frame_rate);
+        time.set_time(h, m, s, f);
+        time
+    }
+
+    #[test]
+    fn encode_quarter_frames() {
+        let messages = quarter_frames(&time(FrameRate::Fps25, 1, 2, 3, 4)).unwrap();
+        let data: Vec<u8> = messages.iter().map(|m| m[1]).collect();
+        assert_eq!(data, [0x04, 0x10, 0x23, 0x30, 0x42, 0x50, 0x61, 0x72]);
+        let messages = quarter_frames(&time(FrameRate::Fps29_97Df, 23, 59, 59, 29)).unwrap();
+        let data: Vec<u8> = messages.iter().map(|m| m[1]).collect();
+        assert_eq!(data, [0x0D, 0x11, 0x2B, 0x33, 0x4B, 0x53, 0x67, 0x75]);
+        assert_eq!(
+            quarter_frames(&TimecodeInstant::new(FrameRate::Fps50)),
+            Err(MtcError::UnsupportedFrameRate(FrameRate::Fps50))
+        );
+    }
+
+    #[test]
+    fn full_frame_round_trip() {
+        let sent = time(FrameRate::Fps30, 12, 34, 56, 7);
+        let message = full_frame(&sent, ALL_DEVICES).unwrap();
+        assert_eq!(
+            message,
+            [0xF0, 0x7F, 0x7F, 0x01, 0x01, 0x6C, 0x22, 0x38, 0x07, 0xF7]
+        );
+        let received = parse_full_frame(&message).unwrap();
+        assert_eq!(received, sent);
+        assert_eq!(received.frame_rate, FrameRate::Fps30);
+        assert_eq!(
+            parse_full_frame(&message[..9]),
+            Err(MtcError::InvalidMessage)
+        );
+    }
+
+    #[test]
+    fn reassemble_forward() {
+        let mut reassembler = MtcReassembler::new();
+        let mut sent = time(FrameRate::Fps25, 0, 59, 59, 20);
+        let messages = quarter_frames(&sent).unwrap();
+        // Joining mid-cycle yields nothing until a whole cycle has been seen.
+        for message in &messages[4..] {
+            assert_eq!(reassembler.push(message), None);
+        }
+        for _ in 0..10 {
+            let messages = quarter_frames(&sent).unwrap();
+            for message in &messages[..7] {
+                assert_eq!(reassembler.push(message), None);
+            }
+            sent.set_frame_count(sent.frame_count() + 2);
+            assert_eq!(reassembler.push(&messages[7]), Some(sent.clone()));
+        }
+        assert_eq!(sent.to_string(), "01:00:00:15");
+        assert_eq!(reassembler.direction, TimecodeDirection::Forward);
+    }
+
+    #[test]
+    fn reassemble_reverse() {
+        let mut reassembler = MtcReassembler::new();
+        let mut sent = time(FrameRate::Fps29_97Df, 0, 10, 0, 2);
+        for _ in 0..5 {
+            let messages = quarter_frames(&sent).unwrap();
+            for message in messages[1..].iter().rev() {
+                assert_eq!(reassembler.push(message), None);
+            }
+            sent.set_frame_count(sent.frame_count() - 2);
+            assert_eq!(reassembler.push(&messages[0]), Some(sent.clone()));
+        }
+        assert_eq!(sent.to_string(), "00:09:59;22");
+        assert_eq!(reassembler.direction, TimecodeDirection::Reverse);
+    }
+
+    #[test]
+    fn user_bits() {
+        let user_bits = UserBits::from_chars(*b"R01B");
+        let message = user_bits_message(&user_bits, ALL_DEVICES);
+        assert_eq!(
+            message,
+            [
+                0xF0, 0x7F, 0x7F, 0x01, 0x02, 0x2, 0x5, 0x0, 0x3, 0x1, 0x3, 0x2, 0x4, 0x01, 0xF7
+            ]
+        );
+        assert_eq!(parse_user_bits(&message), Ok(user_bits));
+
+        let mut reassembler = MtcReassembler::new();
+        assert_eq!(reassembler.push(&message), None);
+        let sent = time(FrameRate::Fps25, 1, 0, 0, 0);
+        let received = reassembler.push(&full_frame(&sent, 0).unwrap()).unwrap();
+        assert_eq!(received.user_bits, user_bits);
+    }
+
+    #[test]
+    fn full_frame_restarts_cycle() {
+        let mut reassembler = MtcReassembler::new();
+        let messages = quarter_frames(&time(FrameRate::Fps24, 1, 0, 0, 0)).unwrap();
+        for message in &messages[..5] {
+            reassembler.push(message);
+        }
+        let located = time(FrameRate::Fps24, 2, 0, 0, 0);
+        let full = full_frame(&located, 0).unwrap();
+        assert_eq!(reassembler.push(&full), Some(located));
+        for message in &messages[5..] {
+            assert_eq!(reassembler.push(message), None);
+        }
+    }
+}
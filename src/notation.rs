@@ -0,0 +1,644 @@
+use std::{error::Error, fmt};
+
+use crate::{
+    builder::CueBuilder,
+    cue::{BeatEvent, Cue, JumpRequirement, TempoCurve, TimeSignature},
+};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum NotationErrorKind {
+    InvalidMeter(String),
+    InvalidNumber(String),
+    UnexpectedToken(String),
+    MissingLength,
+    /// A tempo of 0 BPM.
+    InvalidTempo(String),
+    /// A tempo change over a section without beats.
+    EmptyRamp,
+    /// A vamp or repeat of a section without beats.
+    EmptyLoop,
+    /// A quoted label without its closing quote.
+    UnterminatedLabel,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct NotationError {
+    /// Line and column of the offending token, counting from 1.
+    pub line: usize,
+    pub column: usize,
+    pub kind: NotationErrorKind,
+}
+
+impl fmt::Display for NotationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}: ", self.line, self.column)?;
+        match &self.kind {
+            NotationErrorKind::InvalidMeter(token) => write!(f, "Invalid meter '{token}'"),
+            NotationErrorKind::InvalidNumber(token) => write!(f, "Invalid number '{token}'"),
+            NotationErrorKind::UnexpectedToken(token) => write!(f, "Unexpected '{token}'"),
+            NotationErrorKind::MissingLength => write!(f, "Section has no length"),
+            NotationErrorKind::InvalidTempo(token) => {
+                write!(f, "Invalid tempo '{token}', must be at least 1 BPM")
+            }
+            NotationErrorKind::EmptyRamp => write!(f, "Tempo change over a section with no beats"),
+            NotationErrorKind::EmptyLoop => write!(f, "Loop over a section with no beats"),
+            NotationErrorKind::UnterminatedLabel => write!(f, "Label has no closing quote"),
+        }
+    }
+}
+
+impl Error for NotationError {}
+
+struct Token<'a> {
+    text: &'a str,
+    line: usize,
+    column: usize,
+}
+
+impl Token<'_> {
+    fn error(&self, kind: NotationErrorKind) -> NotationError {
+        NotationError {
+            line: self.line,
+            column: self.column,
+            kind,
+        }
+    }
+
+    fn number(&self, text: &str) -> Result<usize, NotationError> {
+        text.parse()
+            .map_err(|_| self.error(NotationErrorKind::InvalidNumber(self.text.to_string())))
+    }
+
+    fn tempo(&self, text: &str) -> Result<usize, NotationError> {
+        match self.number(text)? {
+            0 => Err(self.error(NotationErrorKind::InvalidTempo(self.text.to_string()))),
+            tempo => Ok(tempo),
+        }
+    }
+}
+
+/// Keywords for the curve of a ramp. Ramps without one change beat length linearly.
+const CURVES: [(&str, TempoCurve); 5] = [
+    ("linear-tempo", TempoCurve::LinearTempo),
+    ("exponential", TempoCurve::Exponential),
+    ("ease-in", TempoCurve::EaseIn),
+    ("ease-out", TempoCurve::EaseOut),
+    ("s-curve", TempoCurve::SCurve),
+];
+
+#[derive(Default)]
+struct Section {
+    label: Option<String>,
+    meter: Option<(u8, u8)>,
+    length: Option<(usize, usize)>,
+    tempo: Option<usize>,
+    ramp: Option<usize>,
+    /// Beats the ramp runs over, the whole section if not given.
+    ramp_length: Option<usize>,
+    curve: Option<TempoCurve>,
+    vamp: bool,
+    repeat: Option<usize>,
+}
+
+/// Statements of a line as (column, text), split at `;` with comments removed. Quoted labels may
+/// contain either.
+fn statements(line: &str) -> Vec<(usize, &str)> {
+    let mut statements = vec![];
+    let mut start = 0;
+    let mut quoted = false;
+    let mut escaped = false;
+    for (idx, c) in line.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' if quoted => escaped = true,
+            '"' => quoted = !quoted,
+            ';' if !quoted => {
+                statements.push((start, &line[start..idx]));
+                start = idx + 1;
+            }
+            '#' if !quoted => {
+                statements.push((start, &line[start..idx]));
+                return statements;
+            }
+            _ => {}
+        }
+    }
+    statements.push((start, &line[start..]));
+    statements
+}
+
+/// Text of a quoted label, after its opening quote, and the length up to and including its
+/// closing quote.
+fn unquote(text: &str) -> Option<(String, usize)> {
+    let mut label = String::new();
+    let mut escaped = false;
+    for (idx, c) in text.char_indices() {
+        match c {
+            _ if escaped => {
+                label.push(c);
+                escaped = false;
+            }
+            '\\' => escaped = true,
+            '"' => return Some((label, idx + 1)),
+            _ => label.push(c),
+        }
+    }
+    None
+}
+
+/// Label as written by `print`, quoted if it would not read back as it is.
+fn quote(label: &str) -> String {
+    if label.contains([':', ';', '#', '"']) || label.trim() != label {
+        format!("\"{}\"", label.replace('\\', "\\\\").replace('"', "\\\""))
+    } else {
+        label.to_string()
+    }
+}
+
+fn tokens<'a>(statement: &'a str, offset: usize, line: usize) -> Vec<Token<'a>> {
+    let mut tokens = vec![];
+    let mut start = None;
+    for (idx, c) in statement.char_indices().chain([(statement.len(), ' ')]) {
+        match (start, c.is_whitespace()) {
+            (None, false) => start = Some(idx),
+            (Some(from), true) => {
+                tokens.push(Token {
+                    text: &statement[from..idx],
+                    line,
+                    column: offset + from + 1,
+                });
+                start = None;
+            }
+            _ => {}
+        }
+    }
+    tokens
+}
+
+fn parse_meter(token: &Token) -> Result<(u8, u8), NotationError> {
+    let invalid = || token.error(NotationErrorKind::InvalidMeter(token.text.to_string()));
+    let (numerator, denominator) = token.text.split_once('/').ok_or_else(invalid)?;
+    match (numerator.parse(), denominator.parse()) {
+        (Ok(numerator @ 1..), Ok(denominator @ 1..)) => Ok((numerator, denominator)),
+        _ => Err(invalid()),
+    }
+}
+
+fn parse_section(statement: &str, offset: usize, line: usize) -> Result<Section, NotationError> {
+    let mut section = Section::default();
+    let (mut statement, mut offset) = (statement, offset);
+    let unquoted = statement.trim_start();
+    if let Some(quoted) = unquoted.strip_prefix('"') {
+        let quote_offset = offset + statement.len() - unquoted.len();
+        let error = |column, kind| NotationError { line, column, kind };
+        let (label, len) = unquote(quoted)
+            .ok_or_else(|| error(quote_offset + 1, NotationErrorKind::UnterminatedLabel))?;
+        let after = &quoted[len..];
+        let Some(rest) = after.trim_start().strip_prefix(':') else {
+            let token = after.split_whitespace().next().unwrap_or_default();
+            let column = quote_offset + 2 + len + after.len() - after.trim_start().len();
+            return Err(error(
+                column,
+                NotationErrorKind::UnexpectedToken(token.to_string()),
+            ));
+        };
+        section.label = Some(label);
+        offset += statement.len() - rest.len();
+        statement = rest;
+    } else if let Some((label, rest)) = statement.split_once(':') {
+        section.label = Some(label.trim().to_string());
+        offset += label.len() + 1;
+        statement = rest;
+    }
+    let tokens = tokens(statement, offset, line);
+    let Some(first) = tokens.first() else {
+        return match section.label {
+            Some(_) => Err(NotationError {
+                line,
+                column: offset + 1,
+                kind: NotationErrorKind::MissingLength,
+            }),
+            None => Ok(section),
+        };
+    };
+
+    if first.text == "vamp" {
+        return match &tokens[1..] {
+            [count, unit] if matches!(unit.text, "bar" | "bars") => {
+                match count.number(count.text)? {
+                    0 => Err(first.error(NotationErrorKind::EmptyLoop)),
+                    bars => {
+                        section.length = Some((bars, 0));
+                        section.vamp = true;
+                        Ok(section)
+                    }
+                }
+            }
+            [_, unit, ..] | [unit] => {
+                Err(unit.error(NotationErrorKind::UnexpectedToken(unit.text.to_string())))
+            }
+            [] => Err(first.error(NotationErrorKind::MissingLength)),
+        };
+    }
+
+    let mut ramp_token = None;
+    let mut curve_token = None;
+    let mut loop_token = None;
+    let mut over_token = None;
+    let mut rest = tokens.iter();
+    while let Some(token) = rest.next() {
+        let text = token.text;
+        if text.contains('/') && section.meter.is_none() && section.length.is_none() {
+            section.meter = Some(parse_meter(token)?);
+        } else if let Some(length) = text.strip_prefix('x') {
+            let (bars, beats) = length.split_once('+').unwrap_or((length, "0"));
+            section.length = Some((token.number(bars)?, token.number(beats)?));
+        } else if let Some(tempo) = text.strip_prefix('@') {
+            section.tempo = Some(token.tempo(tempo)?);
+        } else if let Some(tempo) = ["rit->", "accel->", "->"]
+            .iter()
+            .find_map(|prefix| text.strip_prefix(prefix))
+        {
+            section.ramp = Some(token.tempo(tempo)?);
+            ramp_token = Some(token);
+        } else if let Some((_, curve)) = CURVES.iter().find(|(name, _)| *name == text) {
+            section.curve = Some(*curve);
+            curve_token = Some(token);
+        } else if text == "over" {
+            let length = rest
+                .next()
+                .ok_or_else(|| token.error(NotationErrorKind::UnexpectedToken(text.to_string())))?;
+            match length.number(length.text)? {
+                0 => return Err(length.error(NotationErrorKind::EmptyRamp)),
+                beats => section.ramp_length = Some(beats),
+            }
+            over_token = Some(token);
+        } else if text == "vamp" {
+            section.vamp = true;
+            loop_token = Some(token);
+        } else if text == "repeat" {
+            loop_token = Some(token);
+            let times = rest
+                .next()
+                .ok_or_else(|| token.error(NotationErrorKind::UnexpectedToken(text.to_string())))?;
+            section.repeat = Some(times.number(times.text)?);
+        } else {
+            return Err(token.error(NotationErrorKind::UnexpectedToken(text.to_string())));
+        }
+    }
+    if section.length.is_none() {
+        return Err(first.error(NotationErrorKind::MissingLength));
+    }
+    if section.length == Some((0, 0)) {
+        if let Some(token) = ramp_token {
+            return Err(token.error(NotationErrorKind::EmptyRamp));
+        }
+        if let Some(token) = loop_token {
+            return Err(token.error(NotationErrorKind::EmptyLoop));
+        }
+    }
+    for token in [curve_token, over_token].into_iter().flatten() {
+        if section.ramp.is_none() {
+            return Err(token.error(NotationErrorKind::UnexpectedToken(token.text.to_string())));
+        }
+    }
+    Ok(section)
+}
+
+/// Builds a cue from its text notation, one section per line or `;`:
+///
+/// ```text
+/// A: 4/4 x8 @120; B: 7/8 x4 rit->96
+/// vamp 2 bars   # loops while the VLT is on
+/// ```
+///
+/// A section is an optional `label:`, a meter (the previous one if left out), a length of `xN`
+/// bars or `xN+M` for N bars and M beats, and any of `@bpm`, `rit->bpm`, `accel->bpm`, `vamp`
+/// and `repeat N`. Labels containing `:`, `;` or `#` are written in double quotes, with `\"` and
+/// `\\` for a quote and a backslash.
+///
+/// A ramp starts from the `@` tempo if there is one and runs over the whole section, or over N
+/// beats with `over N`, which may carry on into the following sections. It may be followed by its
+/// curve: `linear-tempo`, `exponential`, `ease-in`, `ease-out` or `s-curve`. A repeat plays the
+/// section N more times. `vamp N bars` adds a vamp in the current meter.
+pub fn parse(text: &str) -> Result<Cue, NotationError> {
+    let mut builder = CueBuilder::new("");
+    let mut meter = (4, 4);
+    for (line_idx, line) in text.lines().enumerate() {
+        for (offset, statement) in statements(line) {
+            let section = parse_section(statement, offset, line_idx + 1)?;
+            let Some((bars, beats)) = section.length else {
+                continue;
+            };
+            meter = section.meter.unwrap_or(meter);
+            let beats = bars * meter.0 as usize + beats;
+            if let Some(label) = &section.label {
+                builder = builder.mark(label);
+            }
+            let ramp_length = section.ramp_length.unwrap_or(beats);
+            builder =
+                match (section.tempo, section.ramp) {
+                    (Some(from), Some(to)) => builder
+                        .curve(section.curve.unwrap_or_default())
+                        .ramp(from, to, ramp_length),
+                    (None, Some(to)) => builder
+                        .curve(section.curve.unwrap_or_default())
+                        .ramp_to(to, ramp_length),
+                    (Some(tempo), None) => builder.tempo(tempo),
+                    (None, None) => builder,
+                };
+            builder = builder.bars(0, meter).beats(beats);
+            if section.vamp {
+                builder = builder.vamp();
+            }
+            if let Some(times) = section.repeat {
+                builder = builder.repeat(times);
+            }
+        }
+    }
+    Ok(builder.build())
+}
+
+/// Writes the tempo map of a cue in text notation, one section per line. Events other than
+/// rehearsal marks, time signatures, tempo changes and vamps are left out.
+pub fn print(cue: &Cue) -> String {
+    // Sections start at events, jump destinations, after jumps and where ramps end.
+    let mut starts = vec![false; cue.beats.len() + 1];
+    starts[0] = true;
+    for (idx, beat) in cue.beats.iter().enumerate() {
+        for event in &beat.events {
+            match event {
+                BeatEvent::RehearsalMarkEvent { .. }
+                | BeatEvent::TimeSignatureEvent { .. }
+                | BeatEvent::TempoChangeEvent { .. } => starts[idx] = true,
+                BeatEvent::GradualTempoChangeEvent { length, .. } => {
+                    starts[idx] = true;
+                    starts[(idx + length).min(cue.beats.len())] = true;
+                }
+                BeatEvent::JumpEvent { destination, .. } => {
+                    starts[(*destination).min(cue.beats.len())] = true;
+                    starts[idx + 1] = true;
+                }
+                _ => {}
+            }
+        }
+    }
+    let starts: Vec<usize> = (0..cue.beats.len()).filter(|idx| starts[*idx]).collect();
+
+    let mut tempo = 120;
+    let mut lines = vec![];
+    for (section, start) in starts.iter().enumerate() {
+        let end = starts.get(section + 1).copied().unwrap_or(cue.beats.len());
+        let time_signature: TimeSignature = cue.time_signature_at(*start);
+        let numerator = time_signature.numerator.max(1) as usize;
+        let mut line = String::new();
+        for event in &cue.beats[*start].events {
+            if let BeatEvent::RehearsalMarkEvent { label } = event {
+                line = format!("{}: ", quote(label));
+            }
+        }
+        line += &format!(
+            "{}/{} x{}",
+            time_signature.numerator,
+            time_signature.denominator,
+            (end - start) / numerator
+        );
+        if (end - start) % numerator != 0 {
+            line += &format!("+{}", (end - start) % numerator);
+        }
+        for event in &cue.beats[*start].events {
+            match event {
+                BeatEvent::TempoChangeEvent { tempo: new_tempo } => {
+                    tempo = *new_tempo;
+                    line += &format!(" @{tempo}");
+                }
+                BeatEvent::GradualTempoChangeEvent {
+                    start_tempo,
+                    end_tempo,
+                    length,
+                    curve,
+                } => {
+                    if *start_tempo != tempo {
+                        line += &format!(" @{start_tempo}");
+                    }
+                    let direction = if end_tempo < start_tempo {
+                        "rit"
+                    } else {
+                        "accel"
+                    };
+                    line += &format!(" {direction}->{end_tempo}");
+                    if *length != end - start {
+                        line += &format!(" over {length}");
+                    }
+                    if let Some((name, _)) = CURVES.iter().find(|(_, c)| c == curve) {
+                        line += &format!(" {name}");
+                    }
+                    tempo = *end_tempo;
+                }
+                _ => {}
+            }
+        }
+        for event in &cue.beats[end - 1].events {
+            match event {
+                BeatEvent::JumpEvent {
+                    destination,
+                    requirement: JumpRequirement::JumpModeOn,
+                    ..
+                } if destination == start => line += " vamp",
+                BeatEvent::JumpEvent {
+                    destination,
+                    requirement: JumpRequirement::Repeat { times },
+                    ..
+                } if destination == start => line += &format!(" repeat {times}"),
+                _ => {}
+            }
+        }
+        lines.push(line);
+    }
+    lines.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_and_print() {
+        let cue = parse("A: 4/4 x8 @120; B: 7/8 x4 rit->96; vamp 2 bars").unwrap();
+        assert_eq!(cue.beats.len(), 32 + 28 + 14);
+        assert_eq!(cue.beats[32].bar_number, 9);
+        assert_eq!(cue.beats[60].bar_number, 13);
+        assert_eq!(cue.beats[59].tempo(), 97);
+        assert_eq!(cue.beats[60].tempo(), 96);
+        assert!(matches!(
+            cue.beats[73].events[..],
+            [BeatEvent::JumpEvent {
+                destination: 60,
+                ..
+            }]
+        ));
+        let text = print(&cue);
+        assert_eq!(text, "A: 4/4 x8 @120\nB: 7/8 x4 rit->96\n7/8 x2 vamp");
+        assert_eq!(parse(&text).unwrap(), cue);
+    }
+
+    #[test]
+    fn multiline() {
+        let text = "\
+Intro: 3/4 x2+1 @90   # pickup
+x4 accel->120
+Coda: 5/4 x1 @60 vamp
+";
+        let cue = parse(text).unwrap();
+        assert_eq!(cue.beats.len(), 7 + 12 + 5);
+        assert_eq!(cue.time_signature_at(19).to_string(), "5/4");
+        assert_eq!(
+            print(&cue),
+            "Intro: 3/4 x2+1 @90\n3/4 x4 accel->120\nCoda: 5/4 x1 @60 vamp"
+        );
+
+        let cue = parse("A: 4/4 x2 @100 repeat 3; B: x1").unwrap();
+        assert_eq!(cue.simulate(false).path.len(), 4 * 8 + 4);
+        assert_eq!(print(&cue), "A: 4/4 x2 @100 repeat 3\nB: 4/4 x1");
+    }
+
+    #[test]
+    fn ramp_from_tempo() {
+        let cue = parse("x1 @120; x2 @90 rit->60").unwrap();
+        assert!(
+            cue.beats[4].events
+                == [BeatEvent::GradualTempoChangeEvent {
+                    start_tempo: 90,
+                    end_tempo: 60,
+                    length: 8,
+                    curve: TempoCurve::LinearPeriod,
+                }]
+        );
+        assert_eq!(cue.beats[4].tempo(), 90);
+        assert!(cue.lint().is_empty());
+        assert_eq!(print(&cue), "4/4 x1 @120\n4/4 x2 @90 rit->60");
+    }
+
+    #[test]
+    fn marks_inside_ramps() {
+        let cue = CueBuilder::new("")
+            .tempo(120)
+            .bars(1, (4, 4))
+            .ramp_to(60, 8)
+            .beats(4)
+            .mark("B")
+            .beats(4)
+            .build();
+        let text = print(&cue);
+        assert_eq!(text, "4/4 x1 @120\n4/4 x1 rit->60 over 8\nB: 4/4 x1");
+        assert_eq!(parse(&text).unwrap(), cue);
+        assert_eq!(cue.beats[11].length, 937500);
+    }
+
+    #[test]
+    fn quoted_labels() {
+        let labels = [
+            "Verse: 2",
+            "Intro; quiet",
+            "#1",
+            "\"Hey\" \\o/",
+            " Coda",
+            "A\\B",
+        ];
+        let mut builder = CueBuilder::new("");
+        for label in labels {
+            builder = builder.mark(label).bars(1, (4, 4));
+        }
+        let cue = builder.build();
+        let text = print(&cue);
+        assert_eq!(text.lines().next(), Some("\"Verse: 2\": 4/4 x1"));
+        assert_eq!(text.lines().last(), Some("A\\B: 4/4 x1"));
+        assert_eq!(parse(&text).unwrap(), cue);
+        assert_eq!(parse(&text.replace('\n', "; ")).unwrap(), cue);
+    }
+
+    #[test]
+    fn curves() {
+        let text = "A: 4/4 x2 @100 rit->60 s-curve\nB: 4/4 x2 accel->90\n4/4 x1 accel->120 ease-in";
+        let cue = parse(text).unwrap();
+        let curves: Vec<TempoCurve> = cue
+            .beats
+            .iter()
+            .flat_map(|beat| &beat.events)
+            .filter_map(|event| match event {
+                BeatEvent::GradualTempoChangeEvent { curve, .. } => Some(*curve),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(
+            curves,
+            [
+                TempoCurve::SCurve,
+                TempoCurve::LinearPeriod,
+                TempoCurve::EaseIn
+            ]
+        );
+        assert_eq!(print(&cue), text);
+        assert_eq!(parse(&print(&cue)).unwrap(), cue);
+    }
+
+    #[test]
+    fn errors() {
+        let error = |text| parse(text).unwrap_err();
+        assert_eq!(
+            error("A: 4/4 x8\nB: 4/x x2"),
+            NotationError {
+                line: 2,
+                column: 4,
+                kind: NotationErrorKind::InvalidMeter("4/x".to_string())
+            }
+        );
+        assert_eq!(
+            error("4/4 x8; 3/4 @100").to_string(),
+            "1:9: Section has no length"
+        );
+        assert_eq!(error("x8 @fast").to_string(), "1:4: Invalid number '@fast'");
+        assert_eq!(error("x8 rall").to_string(), "1:4: Unexpected 'rall'");
+        assert_eq!(error("vamp 2 beats").to_string(), "1:8: Unexpected 'beats'");
+        assert_eq!(error("x2 repeat").to_string(), "1:4: Unexpected 'repeat'");
+        assert_eq!(
+            error("x1 @0").to_string(),
+            "1:4: Invalid tempo '@0', must be at least 1 BPM"
+        );
+        assert_eq!(
+            error("x1\nx4 rit->0"),
+            NotationError {
+                line: 2,
+                column: 4,
+                kind: NotationErrorKind::InvalidTempo("rit->0".to_string())
+            }
+        );
+        assert_eq!(
+            error("A: 4/4 x0 rit->60; B: x1").to_string(),
+            "1:11: Tempo change over a section with no beats"
+        );
+        assert_eq!(error("x2 s-curve").to_string(), "1:4: Unexpected 's-curve'");
+        assert_eq!(
+            error("A: 4/4 x4; x0 vamp").to_string(),
+            "1:15: Loop over a section with no beats"
+        );
+        assert_eq!(
+            error("x4\nvamp 0 bars").to_string(),
+            "2:1: Loop over a section with no beats"
+        );
+        assert_eq!(
+            error("x4; x0+0 repeat 2").to_string(),
+            "1:10: Loop over a section with no beats"
+        );
+        assert_eq!(error("x4 over 2").to_string(), "1:4: Unexpected 'over'");
+        assert_eq!(
+            error("x4 rit->60 over 0").to_string(),
+            "1:17: Tempo change over a section with no beats"
+        );
+        assert_eq!(
+            error("x1; \"A: x1").to_string(),
+            "1:5: Label has no closing quote"
+        );
+        assert_eq!(error("\"A\" x1").to_string(), "1:5: Unexpected 'x1'");
+    }
+}
@@ -0,0 +1,339 @@
+use std::{error::Error, fmt};
+
+use serde::{Deserialize, Serialize};
+
+const BUNDLE_TAG: &[u8] = b"#bundle\0";
+/// Time tag meaning "as soon as received".
+pub const IMMEDIATELY: u64 = 1;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum OscError {
+    UnexpectedEnd,
+    InvalidAddress,
+    InvalidString,
+    InvalidTypeTags,
+    UnsupportedType(char),
+    InvalidSize(i32),
+}
+
+impl fmt::Display for OscError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OscError::UnexpectedEnd => write!(f, "OSC packet ends unexpectedly"),
+            OscError::InvalidAddress => write!(f, "OSC address must start with '/'"),
+            OscError::InvalidString => write!(f, "OSC string is not valid UTF-8"),
+            OscError::InvalidTypeTags => write!(f, "OSC type tags must start with ','"),
+            OscError::UnsupportedType(tag) => write!(f, "Unsupported OSC type tag '{tag}'"),
+            OscError::InvalidSize(size) => write!(f, "Invalid OSC element size {size}"),
+        }
+    }
+}
+
+impl Error for OscError {}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum OscArgument {
+    Int(i32),
+    Float(f32),
+    String(String),
+    Blob(Vec<u8>),
+    Long(i64),
+    Double(f64),
+    True,
+    False,
+    Nil,
+}
+
+impl OscArgument {
+    fn type_tag(&self) -> char {
+        match self {
+            OscArgument::Int(_) => 'i',
+            OscArgument::Float(_) => 'f',
+            OscArgument::String(_) => 's',
+            OscArgument::Blob(_) => 'b',
+            OscArgument::Long(_) => 'h',
+            OscArgument::Double(_) => 'd',
+            OscArgument::True => 'T',
+            OscArgument::False => 'F',
+            OscArgument::Nil => 'N',
+        }
+    }
+}
+
+impl fmt::Display for OscArgument {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OscArgument::Int(value) => write!(f, "{value}"),
+            OscArgument::Float(value) => write!(f, "{value}"),
+            OscArgument::String(value) => write!(f, "\"{value}\""),
+            OscArgument::Blob(data) => write!(f, "<{} bytes>", data.len()),
+            OscArgument::Long(value) => write!(f, "{value}"),
+            OscArgument::Double(value) => write!(f, "{value}"),
+            OscArgument::True => write!(f, "true"),
+            OscArgument::False => write!(f, "false"),
+            OscArgument::Nil => write!(f, "nil"),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct OscMessage {
+    /// Address pattern, e.g. `/eos/cue/1/fire`.
+    pub address: String,
+    pub arguments: Vec<OscArgument>,
+}
+
+impl fmt::Display for OscMessage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.address)?;
+        for argument in &self.arguments {
+            write!(f, " {argument}")?;
+        }
+        Ok(())
+    }
+}
+
+impl OscMessage {
+    pub fn new(address: &str, arguments: Vec<OscArgument>) -> OscMessage {
+        OscMessage {
+            address: address.to_string(),
+            arguments,
+        }
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        let mut out = vec![];
+        write_string(&mut out, &self.address);
+        let tags: String = std::iter::once(',')
+            .chain(self.arguments.iter().map(|a| a.type_tag()))
+            .collect();
+        write_string(&mut out, &tags);
+        for argument in &self.arguments {
+            match argument {
+                OscArgument::Int(value) => out.extend(value.to_be_bytes()),
+                OscArgument::Float(value) => out.extend(value.to_be_bytes()),
+                OscArgument::String(value) => write_string(&mut out, value),
+                OscArgument::Blob(data) => {
+                    out.extend((data.len() as i32).to_be_bytes());
+                    out.extend(data);
+                    pad(&mut out);
+                }
+                OscArgument::Long(value) => out.extend(value.to_be_bytes()),
+                OscArgument::Double(value) => out.extend(value.to_be_bytes()),
+                OscArgument::True | OscArgument::False | OscArgument::Nil => {}
+            }
+        }
+        out
+    }
+
+    pub fn decode(data: &[u8]) -> Result<OscMessage, OscError> {
+        let mut reader = Reader { data, pos: 0 };
+        let address = reader.string()?;
+        if !address.starts_with('/') {
+            return Err(OscError::InvalidAddress);
+        }
+        // Very old senders leave out the type tags of messages without arguments.
+        if reader.pos == data.len() {
+            return Ok(OscMessage::new(&address, vec![]));
+        }
+        let tags = reader.string()?;
+        let Some(tags) = tags.strip_prefix(',') else {
+            return Err(OscError::InvalidTypeTags);
+        };
+        let mut arguments = vec![];
+        for tag in tags.chars() {
+            arguments.push(match tag {
+                'i' => OscArgument::Int(i32::from_be_bytes(reader.array()?)),
+                'f' => OscArgument::Float(f32::from_be_bytes(reader.array()?)),
+                's' => OscArgument::String(reader.string()?),
+                'b' => {
+                    let len = i32::from_be_bytes(reader.array()?);
+                    let len = usize::try_from(len).map_err(|_| OscError::InvalidSize(len))?;
+                    let blob = reader.bytes(len)?.to_vec();
+                    reader.align()?;
+                    OscArgument::Blob(blob)
+                }
+                'h' => OscArgument::Long(i64::from_be_bytes(reader.array()?)),
+                'd' => OscArgument::Double(f64::from_be_bytes(reader.array()?)),
+                'T' => OscArgument::True,
+                'F' => OscArgument::False,
+                'N' => OscArgument::Nil,
+                tag => return Err(OscError::UnsupportedType(tag)),
+            });
+        }
+        Ok(OscMessage::new(&address, arguments))
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum OscPacket {
+    Message(OscMessage),
+    Bundle {
+        /// NTP time tag, `IMMEDIATELY` to act on receipt.
+        time_tag: u64,
+        content: Vec<OscPacket>,
+    },
+}
+
+impl OscPacket {
+    pub fn encode(&self) -> Vec<u8> {
+        match self {
+            OscPacket::Message(message) => message.encode(),
+            OscPacket::Bundle { time_tag, content } => {
+                let mut out = BUNDLE_TAG.to_vec();
+                out.extend(time_tag.to_be_bytes());
+                for element in content {
+                    let element = element.encode();
+                    out.extend((element.len() as i32).to_be_bytes());
+                    out.extend(element);
+                }
+                out
+            }
+        }
+    }
+
+    pub fn decode(data: &[u8]) -> Result<OscPacket, OscError> {
+        let Some(elements) = data.strip_prefix(BUNDLE_TAG) else {
+            return Ok(OscPacket::Message(OscMessage::decode(data)?));
+        };
+        let mut reader = Reader {
+            data: elements,
+            pos: 0,
+        };
+        let time_tag = u64::from_be_bytes(reader.array()?);
+        let mut content = vec![];
+        while reader.pos < elements.len() {
+            let size = i32::from_be_bytes(reader.array()?);
+            if size <= 0 || size % 4 != 0 {
+                return Err(OscError::InvalidSize(size));
+            }
+            content.push(OscPacket::decode(reader.bytes(size as usize)?)?);
+        }
+        Ok(OscPacket::Bundle { time_tag, content })
+    }
+}
+
+/// Null terminated, padded to a multiple of four bytes.
+fn write_string(out: &mut Vec<u8>, value: &str) {
+    out.extend(value.as_bytes());
+    out.push(0);
+    pad(out);
+}
+
+fn pad(out: &mut Vec<u8>) {
+    out.resize(out.len().next_multiple_of(4), 0);
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn bytes(&mut self, len: usize) -> Result<&'a [u8], OscError> {
+        let bytes = self
+            .data
+            .get(self.pos..self.pos + len)
+            .ok_or(OscError::UnexpectedEnd)?;
+        self.pos += len;
+        Ok(bytes)
+    }
+
+    fn array<const N: usize>(&mut self) -> Result<[u8; N], OscError> {
+        Ok(self.bytes(N)?.try_into().unwrap())
+    }
+
+    fn align(&mut self) -> Result<(), OscError> {
+        let len = self.pos.next_multiple_of(4) - self.pos;
+        self.bytes(len).map(|_| ())
+    }
+
+    fn string(&mut self) -> Result<String, OscError> {
+        let rest = &self.data[self.pos.min(self.data.len())..];
+        let len = rest
+            .iter()
+            .position(|byte| *byte == 0)
+            .ok_or(OscError::UnexpectedEnd)?;
+        let value = std::str::from_utf8(&rest[..len])
+            .map_err(|_| OscError::InvalidString)?
+            .to_string();
+        self.pos += len + 1;
+        self.align()?;
+        Ok(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn spec_examples() {
+        let message = OscMessage::new("/oscillator/4/frequency", vec![OscArgument::Float(440.0)]);
+        let mut expected = b"/oscillator/4/frequency\0,f\0\0".to_vec();
+        expected.extend([0x43, 0xDC, 0x00, 0x00]);
+        assert_eq!(message.encode(), expected);
+        assert_eq!(OscMessage::decode(&expected).unwrap(), message);
+
+        let message = OscMessage::new(
+            "/foo",
+            vec![
+                OscArgument::Int(1000),
+                OscArgument::Int(-1),
+                OscArgument::String("hello".to_string()),
+                OscArgument::Float(1.234),
+                OscArgument::Float(5.678),
+            ],
+        );
+        let encoded = message.encode();
+        assert_eq!(encoded.len(), 40);
+        assert_eq!(&encoded[..14], b"/foo\0\0\0\0,iisff");
+        assert_eq!(&encoded[24..32], b"hello\0\0\0");
+        assert_eq!(OscMessage::decode(&encoded).unwrap(), message);
+    }
+
+    #[test]
+    fn bundles() {
+        let packet = OscPacket::Bundle {
+            time_tag: IMMEDIATELY,
+            content: vec![
+                OscPacket::Message(OscMessage::new("/eos/cue/1/fire", vec![])),
+                OscPacket::Message(OscMessage::new(
+                    "/video/clip",
+                    vec![
+                        OscArgument::Blob(vec![1, 2, 3, 4, 5]),
+                        OscArgument::Long(-5),
+                        OscArgument::Double(0.25),
+                        OscArgument::True,
+                        OscArgument::Nil,
+                    ],
+                )),
+            ],
+        };
+        let encoded = packet.encode();
+        assert_eq!(&encoded[..8], BUNDLE_TAG);
+        assert_eq!(encoded.len() % 4, 0);
+        assert_eq!(OscPacket::decode(&encoded).unwrap(), packet);
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!(
+            OscMessage::decode(b"foo\0,\0\0\0"),
+            Err(OscError::InvalidAddress)
+        );
+        assert_eq!(
+            OscMessage::decode(b"/foo\0\0\0\0,i\0\0\0\0"),
+            Err(OscError::UnexpectedEnd)
+        );
+        assert_eq!(
+            OscMessage::decode(b"/foo\0\0\0\0,m\0\0"),
+            Err(OscError::UnsupportedType('m'))
+        );
+        assert_eq!(OscMessage::decode(b"/foo"), Err(OscError::UnexpectedEnd));
+        assert_eq!(
+            OscMessage::decode(b"/foo\0\0\0\0"),
+            Ok(OscMessage::new("/foo", vec![]))
+        );
+    }
+}
@@ -0,0 +1,259 @@
+use std::{
+    error::Error,
+    f32::consts::TAU,
+    fmt,
+    io::{self, Write},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    cue::{AccentLevel, Cue},
+    simulate::{Simulation, SimulationEnd},
+};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum RenderError {
+    /// The simulation stopped at its beat limit, most likely in a loop that never ends.
+    BeatLimit,
+    /// The simulation plays a beat the cue doesn't have, so it came from another cue.
+    BeatOutOfRange(usize),
+}
+
+impl fmt::Display for RenderError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RenderError::BeatLimit => write!(f, "Cue does not finish, not rendering it"),
+            RenderError::BeatOutOfRange(idx) => {
+                write!(
+                    f,
+                    "Simulation plays beat {idx}, which the cue does not have"
+                )
+            }
+        }
+    }
+}
+
+impl Error for RenderError {}
+
+/// A decaying sine burst.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ClickSound {
+    pub frequency: f32,
+    pub level: f32,
+    pub length_ms: u32,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct RenderSettings {
+    pub sample_rate: u32,
+    pub downbeat: ClickSound,
+    pub beat: ClickSound,
+    pub subdivision: ClickSound,
+}
+
+impl Default for RenderSettings {
+    fn default() -> Self {
+        Self {
+            sample_rate: 48000,
+            downbeat: ClickSound {
+                frequency: 1760.0,
+                level: 1.0,
+                length_ms: 30,
+            },
+            beat: ClickSound {
+                frequency: 880.0,
+                level: 0.7,
+                length_ms: 30,
+            },
+            subdivision: ClickSound {
+                frequency: 880.0,
+                level: 0.35,
+                length_ms: 20,
+            },
+        }
+    }
+}
+
+impl RenderSettings {
+    fn sound(&self, level: AccentLevel) -> Option<&ClickSound> {
+        match level {
+            AccentLevel::Downbeat => Some(&self.downbeat),
+            AccentLevel::Beat => Some(&self.beat),
+            AccentLevel::Subdivision => Some(&self.subdivision),
+            AccentLevel::Silent => None,
+        }
+    }
+
+    fn sample_at(&self, time_us: u64) -> usize {
+        ((time_us as u128 * self.sample_rate as u128 + 500000) / 1000000) as usize
+    }
+}
+
+/// Renders the metronome of a cue along a simulated path, one mono sample per frame.
+///
+/// Beats are placed from the running total of beat lengths, so long renders stay in time with the
+/// engine. Every beat sounds its click pattern, and pauses take no time. Simulations that hit
+/// their beat limit are refused rather than rendered at full length.
+pub fn render(
+    cue: &Cue,
+    simulation: &Simulation,
+    settings: &RenderSettings,
+) -> Result<Vec<f32>, RenderError> {
+    if simulation.end == SimulationEnd::BeatLimit {
+        return Err(RenderError::BeatLimit);
+    }
+    let mut total_us = 0;
+    for beat_idx in &simulation.path {
+        let beat = cue
+            .beats
+            .get(*beat_idx)
+            .ok_or(RenderError::BeatOutOfRange(*beat_idx))?;
+        total_us += beat.length as u64;
+    }
+    let mut samples = vec![0.0; settings.sample_at(total_us)];
+    let patterns = cue.click_patterns();
+    let mut time_us = 0;
+    for beat_idx in &simulation.path {
+        let length = cue.beats[*beat_idx].length as u64;
+        let pattern = &patterns[*beat_idx];
+        for (click, level) in pattern.iter().enumerate() {
+            let Some(sound) = settings.sound(*level) else {
+                continue;
+            };
+            let start = settings.sample_at(time_us + length * click as u64 / pattern.len() as u64);
+            let len = (settings.sample_rate as u64 * sound.length_ms as u64 / 1000) as usize;
+            for (i, sample) in samples.iter_mut().skip(start).take(len).enumerate() {
+                let t = i as f32 / settings.sample_rate as f32;
+                let decay = 1.0 - i as f32 / len as f32;
+                *sample += sound.level * decay * decay * (TAU * sound.frequency * t).sin();
+            }
+        }
+        time_us += length;
+    }
+    Ok(samples)
+}
+
+/// Renders the metronome of a cue and writes it as a WAV file. Render errors are returned as
+/// `InvalidInput`.
+pub fn render_wav<W: Write>(
+    out: &mut W,
+    cue: &Cue,
+    simulation: &Simulation,
+    settings: &RenderSettings,
+) -> io::Result<()> {
+    let samples = render(cue, simulation, settings)
+        .map_err(|error| io::Error::new(io::ErrorKind::InvalidInput, error))?;
+    write_wav(out, &samples, settings.sample_rate)
+}
+
+/// Writes mono samples as a 16-bit PCM WAV file, clipping anything outside -1.0..=1.0.
+pub fn write_wav<W: Write>(out: &mut W, samples: &[f32], sample_rate: u32) -> io::Result<()> {
+    let data_len = samples.len() as u32 * 2;
+    out.write_all(b"RIFF")?;
+    out.write_all(&(36 + data_len).to_le_bytes())?;
+    out.write_all(b"WAVEfmt ")?;
+    out.write_all(&16u32.to_le_bytes())?;
+    out.write_all(&1u16.to_le_bytes())?;
+    out.write_all(&1u16.to_le_bytes())?;
+    out.write_all(&sample_rate.to_le_bytes())?;
+    out.write_all(&(sample_rate * 2).to_le_bytes())?;
+    out.write_all(&2u16.to_le_bytes())?;
+    out.write_all(&16u16.to_le_bytes())?;
+    out.write_all(b"data")?;
+    out.write_all(&data_len.to_le_bytes())?;
+    let mut data = Vec::with_capacity(data_len as usize);
+    for sample in samples {
+        let value = (sample.clamp(-1.0, 1.0) * i16::MAX as f32).round() as i16;
+        data.extend(value.to_le_bytes());
+    }
+    out.write_all(&data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        cue::{Beat, BeatEvent, JumpModeChange, JumpRequirement},
+        simulate::Simulator,
+    };
+
+    fn cue() -> Cue {
+        let mut cue = Cue::empty();
+        cue.beats = (0..8).map(|_| Beat::empty()).collect();
+        cue.beats[0]
+            .events
+            .push(BeatEvent::TempoChangeEvent { tempo: 120 });
+        cue.beats[4]
+            .events
+            .push(BeatEvent::TempoChangeEvent { tempo: 60 });
+        cue.beats[7].events.push(BeatEvent::JumpEvent {
+            destination: 4,
+            requirement: JumpRequirement::JumpModeOn,
+            when_jumped: JumpModeChange::SetOff,
+            when_passed: JumpModeChange::None,
+        });
+        for (i, beat) in cue.beats.iter_mut().enumerate() {
+            beat.bar_number = i / 4 + 1;
+            beat.count = (i % 4 + 1) as u8;
+        }
+        cue.recalculate_tempo_changes();
+        cue
+    }
+
+    fn peak(samples: &[f32]) -> f32 {
+        samples.iter().fold(0.0, |peak, s| peak.max(s.abs()))
+    }
+
+    #[test]
+    fn click_positions() {
+        let settings = RenderSettings::default();
+        let cue = cue();
+        let straight = render(&cue, &cue.simulate(false), &settings).unwrap();
+        assert_eq!(straight.len(), 48000 * 6);
+
+        let looped = render(&cue, &Simulator::new(true).run(&cue), &settings).unwrap();
+        assert_eq!(looped.len(), 48000 * 10);
+        // Clicks start on the beat, with silence after them until the next one.
+        for beat_start in [24000, 96000, 144000, 240000, 288000, 432000] {
+            assert_eq!(looped[beat_start], 0.0);
+            assert!(peak(&looped[beat_start..beat_start + 100]) > 0.1);
+            assert_eq!(peak(&looped[beat_start - 1000..beat_start]), 0.0);
+        }
+        // Count 1 is accented.
+        assert!(peak(&looped[..1440]) > 0.9);
+        assert!(peak(&looped[24000..25440]) < 0.75);
+    }
+
+    #[test]
+    fn unplayable_simulations() {
+        let settings = RenderSettings::default();
+        let cue = Cue::example_loop();
+        let endless = cue.simulate(false);
+        assert_eq!(
+            render(&cue, &endless, &settings),
+            Err(RenderError::BeatLimit)
+        );
+        let error = render_wav(&mut vec![], &cue, &endless, &settings).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+
+        let other = self::cue().simulate(false);
+        let mut short = Cue::empty();
+        short.beats = self::cue().beats[..4].to_vec();
+        assert_eq!(
+            render(&short, &other, &settings),
+            Err(RenderError::BeatOutOfRange(4))
+        );
+    }
+
+    #[test]
+    fn wav_file() {
+        let mut out = vec![];
+        write_wav(&mut out, &[0.0, 1.0, -2.0], 44100).unwrap();
+        assert_eq!(out.len(), 44 + 6);
+        assert_eq!(&out[..4], b"RIFF");
+        assert_eq!(u32::from_le_bytes(out[4..8].try_into().unwrap()), 42);
+        assert_eq!(u32::from_le_bytes(out[24..28].try_into().unwrap()), 44100);
+        assert_eq!(out[44..], [0, 0, 0xFF, 0x7F, 0x01, 0x80]);
+    }
+}
@@ -45,12 +45,12 @@ impl Show {
     pub fn from_file(path: PathBuf) -> Result<Show, serde_json::Error> {
         let file = match std::fs::read(path) {
             Ok(val) => val,
-            Err(err) => return Err(serde_json::Error::custom("could not read file")),
+            Err(_) => return Err(serde_json::Error::custom("could not read file")),
         };
 
         let file_str = match std::str::from_utf8(&file) {
             Ok(val) => val,
-            Err(err) => return Err(serde_json::Error::custom("invalid utf8")),
+            Err(_) => return Err(serde_json::Error::custom("invalid utf8")),
         };
         serde_json::from_str::<Show>(file_str)
     }
@@ -0,0 +1,247 @@
+use serde::{Deserialize, Serialize};
+
+use crate::cue::{
+    BeatEvent, Cue, JumpModeChange, JumpRequirement, PauseEventBehaviour, RepeatCounters,
+};
+
+/// A VLT change made by the operator while the given beat plays for the `occurrence`th time,
+/// counting from zero.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ScriptedVltChange {
+    pub beat_idx: usize,
+    pub occurrence: usize,
+    pub change: JumpModeChange,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SimulationEnd {
+    /// Played past the last beat.
+    Finished,
+    /// A pause event moved on to the next cue.
+    NextCue,
+    /// Stopped after `max_beats` beats, most likely stuck in a loop.
+    BeatLimit,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Simulation {
+    /// Every beat played, in order.
+    pub path: Vec<usize>,
+    /// Positions in `path` after which playback paused for the operator.
+    pub pauses: Vec<usize>,
+    /// Total length of the beats played, not counting time spent paused.
+    pub elapsed_us: u64,
+    pub vlt: bool,
+    pub end: SimulationEnd,
+}
+
+/// Walks a cue the way the engine plays it. Jumps and pauses are decided at the end of each beat,
+/// and scripted VLT changes are applied while their beat plays. Pauses are resumed immediately.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Simulator {
+    pub vlt: bool,
+    pub script: Vec<ScriptedVltChange>,
+    pub max_beats: usize,
+}
+
+impl Simulator {
+    pub fn new(vlt: bool) -> Simulator {
+        Simulator {
+            vlt,
+            script: vec![],
+            max_beats: 100000,
+        }
+    }
+
+    pub fn with_change(
+        mut self,
+        beat_idx: usize,
+        occurrence: usize,
+        change: JumpModeChange,
+    ) -> Self {
+        self.script.push(ScriptedVltChange {
+            beat_idx,
+            occurrence,
+            change,
+        });
+        self
+    }
+
+    pub fn run(&self, cue: &Cue) -> Simulation {
+        let mut sim = Simulation {
+            path: vec![],
+            pauses: vec![],
+            elapsed_us: 0,
+            vlt: self.vlt,
+            end: SimulationEnd::Finished,
+        };
+        let mut visits = vec![0; cue.beats.len()];
+        let mut repeats = RepeatCounters::new();
+        let mut beat_idx = 0;
+        while let Some(beat) = cue.beats.get(beat_idx) {
+            if sim.path.len() >= self.max_beats {
+                sim.end = SimulationEnd::BeatLimit;
+                break;
+            }
+            sim.path.push(beat_idx);
+            sim.elapsed_us += beat.length as u64;
+            for change in self
+                .script
+                .iter()
+                .filter(|c| c.beat_idx == beat_idx && c.occurrence == visits[beat_idx])
+            {
+                sim.vlt = change.change.vlt(sim.vlt);
+            }
+            visits[beat_idx] += 1;
+
+            let mut next = beat_idx + 1;
+            for event in &beat.events {
+                if let BeatEvent::JumpEvent {
+                    destination,
+                    requirement,
+                    when_jumped,
+                    when_passed,
+                } = event
+                {
+                    let jump = match requirement {
+                        JumpRequirement::JumpModeOn => sim.vlt,
+                        JumpRequirement::JumpModeOff => !sim.vlt,
+                        JumpRequirement::None => true,
+                        JumpRequirement::Repeat { times } => repeats.repeat(beat_idx, *times),
+                    };
+                    if jump {
+                        sim.vlt = when_jumped.vlt(sim.vlt);
+                        next = *destination;
+                        break;
+                    }
+                    sim.vlt = when_passed.vlt(sim.vlt);
+                }
+            }
+            for event in &beat.events {
+                if let BeatEvent::PauseEvent { behaviour } = event {
+                    sim.pauses.push(sim.path.len() - 1);
+                    next = match behaviour {
+                        PauseEventBehaviour::Hold => next,
+                        PauseEventBehaviour::RestartBeat => beat_idx,
+                        PauseEventBehaviour::RestartCue => 0,
+                        PauseEventBehaviour::NextCue => {
+                            sim.end = SimulationEnd::NextCue;
+                            return sim;
+                        }
+                        PauseEventBehaviour::Jump { destination } => *destination,
+                    };
+                    break;
+                }
+            }
+            beat_idx = next;
+        }
+        sim
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cue::Beat;
+
+    /// Eight bars of 120 bpm with a two bar vamp over bars 3 and 4, held while the VLT is on. The
+    /// VLT trips as the vamp is left.
+    fn vamp() -> Cue {
+        let mut cue = Cue::empty();
+        cue.beats = (0..32)
+            .map(|_| Beat {
+                length: 500000,
+                ..Beat::empty()
+            })
+            .collect();
+        cue.beats[15].events.push(BeatEvent::JumpEvent {
+            destination: 8,
+            requirement: JumpRequirement::JumpModeOn,
+            when_jumped: JumpModeChange::None,
+            when_passed: JumpModeChange::SetOff,
+        });
+        cue
+    }
+
+    #[test]
+    fn straight_through() {
+        let sim = vamp().simulate(false);
+        assert_eq!(sim.path, (0..32).collect::<Vec<_>>());
+        assert_eq!(sim.elapsed_us, 16000000);
+        assert_eq!(sim.end, SimulationEnd::Finished);
+    }
+
+    #[test]
+    fn hold_vamp_twice() {
+        // The vamp plays once, then twice more while the VLT is on, and the operator releases it
+        // on the third pass.
+        let sim = Simulator::new(true)
+            .with_change(10, 2, JumpModeChange::SetOff)
+            .run(&vamp());
+        assert_eq!(sim.path.len(), 32 + 2 * 8);
+        assert_eq!(sim.path[16..24], [8, 9, 10, 11, 12, 13, 14, 15]);
+        assert_eq!(sim.elapsed_us, 24000000);
+        assert!(!sim.vlt);
+
+        // Jumping trips the VLT, so the vamp only repeats once.
+        let mut cue = vamp();
+        cue.beats[15].events[0] = BeatEvent::JumpEvent {
+            destination: 8,
+            requirement: JumpRequirement::JumpModeOn,
+            when_jumped: JumpModeChange::SetOff,
+            when_passed: JumpModeChange::None,
+        };
+        assert_eq!(cue.simulate(true).path.len(), 40);
+    }
+
+    #[test]
+    fn counted_repeats() {
+        // Bars 3 and 4 play three more times, then bars 2 to 5 play once more.
+        let mut cue = vamp();
+        cue.beats[15].events[0] = BeatEvent::JumpEvent {
+            destination: 8,
+            requirement: JumpRequirement::Repeat { times: 3 },
+            when_jumped: JumpModeChange::None,
+            when_passed: JumpModeChange::None,
+        };
+        assert_eq!(cue.simulate(true).path.len(), 32 + 3 * 8);
+        cue.beats[19].events.push(BeatEvent::JumpEvent {
+            destination: 4,
+            requirement: JumpRequirement::Repeat { times: 1 },
+            when_jumped: JumpModeChange::None,
+            when_passed: JumpModeChange::None,
+        });
+        let sim = cue.simulate(false);
+        // Up to beat 19 with the inner repeats, back to beat 4 and through the inner repeats again,
+        // then on to the end.
+        assert_eq!(sim.path.len(), (16 + 24 + 4) + (12 + 24 + 4) + 12);
+        assert_eq!(sim.end, SimulationEnd::Finished);
+    }
+
+    #[test]
+    fn pauses() {
+        let mut cue = vamp();
+        cue.beats[3].events.push(BeatEvent::PauseEvent {
+            behaviour: PauseEventBehaviour::Hold,
+        });
+        cue.beats[20].events.push(BeatEvent::PauseEvent {
+            behaviour: PauseEventBehaviour::NextCue,
+        });
+        let sim = cue.simulate(false);
+        assert_eq!(sim.pauses, [3, 20]);
+        assert_eq!(sim.path.len(), 21);
+        assert_eq!(sim.end, SimulationEnd::NextCue);
+    }
+
+    #[test]
+    fn endless_loop() {
+        let sim = Simulator {
+            max_beats: 100,
+            ..Simulator::new(false)
+        }
+        .run(&Cue::example_loop());
+        assert_eq!(sim.end, SimulationEnd::BeatLimit);
+        assert_eq!(sim.path.len(), 100);
+        assert_eq!(sim.path[..5], [0, 1, 2, 3, 0]);
+    }
+}
@@ -0,0 +1,715 @@
+use std::{error::Error, fmt};
+
+use crate::cue::{Beat, BeatEvent, Cue, TimeSignature};
+
+/// Tempo of a file without tempo events, in microseconds per quarter note.
+const DEFAULT_TEMPO: u32 = 500000;
+
+const META: u8 = 0xFF;
+const META_TRACK_NAME: u8 = 0x03;
+const META_MARKER: u8 = 0x06;
+const META_CUE_POINT: u8 = 0x07;
+const META_END_OF_TRACK: u8 = 0x2F;
+const META_TEMPO: u8 = 0x51;
+const META_TIME_SIGNATURE: u8 = 0x58;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SmfError {
+    InvalidHeader,
+    UnsupportedFormat(u16),
+    SmpteDivision,
+    UnexpectedEnd,
+    InvalidStatus(u8),
+    InvalidMetaEvent(u8),
+}
+
+impl fmt::Display for SmfError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SmfError::InvalidHeader => write!(f, "Not a standard MIDI file"),
+            SmfError::UnsupportedFormat(format) => {
+                write!(f, "MIDI file format {format} is not supported")
+            }
+            SmfError::SmpteDivision => write!(f, "SMPTE based MIDI files are not supported"),
+            SmfError::UnexpectedEnd => write!(f, "MIDI file ends unexpectedly"),
+            SmfError::InvalidStatus(status) => write!(f, "Invalid MIDI status byte {status:#04x}"),
+            SmfError::InvalidMetaEvent(kind) => write!(f, "Malformed meta event {kind:#04x}"),
+        }
+    }
+}
+
+impl Error for SmfError {}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn new(data: &'a [u8]) -> Reader<'a> {
+        Reader { data, pos: 0 }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.pos >= self.data.len()
+    }
+
+    fn bytes(&mut self, len: usize) -> Result<&'a [u8], SmfError> {
+        let bytes = self
+            .data
+            .get(self.pos..self.pos + len)
+            .ok_or(SmfError::UnexpectedEnd)?;
+        self.pos += len;
+        Ok(bytes)
+    }
+
+    fn u8(&mut self) -> Result<u8, SmfError> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    fn peek(&self) -> Result<u8, SmfError> {
+        self.data
+            .get(self.pos)
+            .copied()
+            .ok_or(SmfError::UnexpectedEnd)
+    }
+
+    fn u32(&mut self) -> Result<u32, SmfError> {
+        let bytes = self.bytes(4)?;
+        Ok(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    /// Variable length quantity of at most four bytes.
+    fn vlq(&mut self) -> Result<u32, SmfError> {
+        let mut value = 0;
+        for _ in 0..4 {
+            let byte = self.u8()?;
+            value = (value << 7) | (byte & 0x7F) as u32;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+        Err(SmfError::UnexpectedEnd)
+    }
+}
+
+/// Meta events of all tracks that matter to a cue, in ticks from the start of the file.
+#[derive(Default)]
+struct TempoMap {
+    name: Option<String>,
+    tempos: Vec<(u64, u32)>,
+    time_signatures: Vec<(u64, TimeSignature)>,
+    marks: Vec<(u64, String)>,
+    end: u64,
+}
+
+impl TempoMap {
+    fn read_track(&mut self, data: &[u8], first: bool) -> Result<(), SmfError> {
+        let mut reader = Reader::new(data);
+        let mut tick = 0;
+        let mut running_status = None;
+        while !reader.is_empty() {
+            tick += reader.vlq()? as u64;
+            self.end = self.end.max(tick);
+            let status = if reader.peek()? & 0x80 != 0 {
+                reader.u8()?
+            } else {
+                running_status.ok_or(SmfError::InvalidStatus(reader.peek()?))?
+            };
+            match status {
+                META => {
+                    let kind = reader.u8()?;
+                    let len = reader.vlq()? as usize;
+                    let data = reader.bytes(len)?;
+                    if kind == META_END_OF_TRACK {
+                        break;
+                    }
+                    self.meta_event(tick, kind, data, first)?;
+                    running_status = None;
+                }
+                0xF0 | 0xF7 => {
+                    let len = reader.vlq()? as usize;
+                    reader.bytes(len)?;
+                    running_status = None;
+                }
+                0x80..=0xEF => {
+                    running_status = Some(status);
+                    reader.bytes(if (0xC0..=0xDF).contains(&status) {
+                        1
+                    } else {
+                        2
+                    })?;
+                }
+                _ => return Err(SmfError::InvalidStatus(status)),
+            }
+        }
+        Ok(())
+    }
+
+    fn meta_event(
+        &mut self,
+        tick: u64,
+        kind: u8,
+        data: &[u8],
+        first: bool,
+    ) -> Result<(), SmfError> {
+        match kind {
+            META_TRACK_NAME if first && self.name.is_none() => {
+                self.name = Some(String::from_utf8_lossy(data).into_owned());
+            }
+            META_MARKER | META_CUE_POINT => {
+                self.marks
+                    .push((tick, String::from_utf8_lossy(data).into_owned()));
+            }
+            META_TEMPO => {
+                let [a, b, c] = data else {
+                    return Err(SmfError::InvalidMetaEvent(kind));
+                };
+                let tempo = u32::from_be_bytes([0, *a, *b, *c]);
+                if tempo == 0 {
+                    return Err(SmfError::InvalidMetaEvent(kind));
+                }
+                self.tempos.push((tick, tempo));
+            }
+            META_TIME_SIGNATURE => {
+                if data.len() < 2 || data[0] == 0 || data[1] > 7 {
+                    return Err(SmfError::InvalidMetaEvent(kind));
+                }
+                self.time_signatures.push((
+                    tick,
+                    TimeSignature {
+                        numerator: data[0],
+                        denominator: 1 << data[1],
+                        grouping: None,
+                    },
+                ));
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+}
+
+/// Builds a cue from a type 0 or 1 standard MIDI file.
+///
+/// Beats follow the time signature denominator, 4/4 until the first time signature event, and
+/// are as long as the tempo map makes them, including tempo changes within a beat. Every beat that
+/// a tempo event falls in gets a `TempoChangeEvent`, and markers and cue points become rehearsal
+/// marks. Marks at the very end of the file go on the last beat.
+///
+/// Tempo events only hold whole BPM, so they can't always express the file's tempo exactly. Each
+/// event carries the nearest BPM to the last tempo in its beat, and
+/// `Cue::recalculate_tempo_changes` replaces the exact beat lengths with the lengths of those
+/// rounded tempos. Lengths only survive recalculation when every tempo is a whole BPM in beats of
+/// the time signature and changes on a beat.
+pub fn read_cue(data: &[u8]) -> Result<Cue, SmfError> {
+    let mut reader = Reader::new(data);
+    if reader.bytes(4).map_err(|_| SmfError::InvalidHeader)? != b"MThd" {
+        return Err(SmfError::InvalidHeader);
+    }
+    let header_len = reader.u32()? as usize;
+    if header_len < 6 {
+        return Err(SmfError::InvalidHeader);
+    }
+    let header = reader.bytes(header_len)?;
+    let format = u16::from_be_bytes([header[0], header[1]]);
+    let division = u16::from_be_bytes([header[4], header[5]]);
+    if format > 1 {
+        return Err(SmfError::UnsupportedFormat(format));
+    }
+    if division & 0x8000 != 0 {
+        return Err(SmfError::SmpteDivision);
+    }
+    if division == 0 {
+        return Err(SmfError::InvalidHeader);
+    }
+
+    let mut map = TempoMap::default();
+    let mut first = true;
+    while !reader.is_empty() {
+        let kind = reader.bytes(4)?;
+        let len = reader.u32()? as usize;
+        let chunk = reader.bytes(len)?;
+        if kind == b"MTrk" {
+            map.read_track(chunk, first)?;
+            first = false;
+        }
+    }
+    Ok(map.into_cue(division as u64))
+}
+
+impl TempoMap {
+    fn into_cue(mut self, ppq: u64) -> Cue {
+        self.tempos.sort_by_key(|(tick, _)| *tick);
+        if self.tempos.first().is_none_or(|(tick, _)| *tick > 0) {
+            self.tempos.insert(0, (0, DEFAULT_TEMPO));
+        }
+        self.time_signatures.sort_by_key(|(tick, _)| *tick);
+        self.marks.sort_by_key(|(tick, _)| *tick);
+
+        // Microseconds × ppq at each tempo event, so beat lengths add up without rounding drift.
+        let mut elapsed = vec![0u128];
+        for pair in self.tempos.windows(2) {
+            let (from, tempo) = pair[0];
+            let to = pair[1].0;
+            elapsed.push(elapsed.last().unwrap() + ((to - from) * tempo as u64) as u128);
+        }
+        let time_at = |tick: u64| -> u64 {
+            let idx = self.tempos.partition_point(|(t, _)| *t <= tick) - 1;
+            let (from, tempo) = self.tempos[idx];
+            let scaled = elapsed[idx] + ((tick - from) * tempo as u64) as u128;
+            ((scaled + ppq as u128 / 2) / ppq as u128) as u64
+        };
+
+        let mut cue = Cue::empty();
+        if let Some(name) = self.name {
+            cue.metadata.name = name;
+        }
+        let mut time_signature = TimeSignature::default();
+        let mut signatures = self.time_signatures.iter().peekable();
+        let mut tempos = self.tempos.iter().peekable();
+        let mut marks = self.marks.iter().peekable();
+        let mut tick = 0;
+        while tick < self.end {
+            let mut events = vec![];
+            let mut changed = tick == 0;
+            while let Some((_, signature)) = signatures.next_if(|(t, _)| *t <= tick) {
+                changed |= *signature != time_signature;
+                time_signature = signature.clone();
+            }
+            if changed {
+                events.push(BeatEvent::TimeSignatureEvent {
+                    numerator: time_signature.numerator,
+                    denominator: time_signature.denominator,
+                    grouping: None,
+                });
+            }
+
+            let beat_ticks = (ppq * 4 / time_signature.denominator as u64).max(1);
+            let mut next = tick + beat_ticks;
+            if let Some((t, _)) = signatures.peek() {
+                next = next.min(*t);
+            }
+
+            let mut tempo = None;
+            while let Some((_, t)) = tempos.next_if(|(t, _)| *t < next) {
+                tempo = Some(*t);
+            }
+            if let Some(tempo) = tempo {
+                let beat_us = tempo as f64 * 4.0 / time_signature.denominator as f64;
+                events.push(BeatEvent::TempoChangeEvent {
+                    tempo: (60000000.0 / beat_us).round() as usize,
+                });
+            }
+            while let Some((_, label)) = marks.next_if(|(t, _)| *t < next) {
+                events.push(BeatEvent::RehearsalMarkEvent {
+                    label: label.clone(),
+                });
+            }
+
+            cue.beats.push(Beat {
+                length: (time_at(next) - time_at(tick)) as usize,
+                events,
+                ..Beat::empty()
+            });
+            tick = next;
+        }
+        if let Some(beat) = cue.beats.last_mut() {
+            beat.events
+                .extend(marks.map(|(_, label)| BeatEvent::RehearsalMarkEvent {
+                    label: label.clone(),
+                }));
+        }
+        cue.reorder_numbers();
+        cue
+    }
+}
+
+/// Notes written for every beat of an exported click track.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ClickNotes {
+    pub channel: u8,
+    pub note: u8,
+    pub velocity: u8,
+    /// Note and velocity on count 1.
+    pub accent_note: u8,
+    pub accent_velocity: u8,
+}
+
+impl Default for ClickNotes {
+    fn default() -> Self {
+        // High and low wood block on the General MIDI percussion channel.
+        Self {
+            channel: 9,
+            note: 77,
+            velocity: 100,
+            accent_note: 76,
+            accent_velocity: 127,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SmfExport {
+    pub ppq: u16,
+    /// Adds a second track with a note on every beat.
+    pub click: Option<ClickNotes>,
+}
+
+impl Default for SmfExport {
+    fn default() -> Self {
+        Self {
+            ppq: 480,
+            click: None,
+        }
+    }
+}
+
+/// Track events with delta times, closed by an end of track event.
+struct TrackWriter {
+    bytes: Vec<u8>,
+    tick: u64,
+}
+
+impl TrackWriter {
+    fn new() -> TrackWriter {
+        TrackWriter {
+            bytes: vec![],
+            tick: 0,
+        }
+    }
+
+    fn event(&mut self, tick: u64, event: &[u8]) {
+        write_vlq(&mut self.bytes, (tick - self.tick) as u32);
+        self.bytes.extend(event);
+        self.tick = tick;
+    }
+
+    fn meta(&mut self, tick: u64, kind: u8, data: &[u8]) {
+        let mut event = vec![META, kind];
+        write_vlq(&mut event, data.len() as u32);
+        event.extend(data);
+        self.event(tick, &event);
+    }
+
+    fn finish(mut self, tick: u64, out: &mut Vec<u8>) {
+        self.meta(tick, META_END_OF_TRACK, &[]);
+        write_chunk(out, b"MTrk", &self.bytes);
+    }
+}
+
+fn write_vlq(out: &mut Vec<u8>, value: u32) {
+    let mut shift = 28;
+    while shift > 0 && value >> shift == 0 {
+        shift -= 7;
+    }
+    while shift > 0 {
+        out.push((value >> shift) as u8 | 0x80);
+        shift -= 7;
+    }
+    out.push(value as u8 & 0x7F);
+}
+
+fn write_chunk(out: &mut Vec<u8>, kind: &[u8], data: &[u8]) {
+    out.extend(kind);
+    out.extend((data.len() as u32).to_be_bytes());
+    out.extend(data);
+}
+
+/// Writes a cue as a type 1 standard MIDI file with its tempo map, time signatures and rehearsal
+/// marks on the first track, and optionally a click track.
+///
+/// Tempo events are written wherever a beat's length changes, so the file plays with the cue's
+/// exact beat lengths. `read_cue` gives back the beat lengths, time signatures, bar numbers and
+/// rehearsal marks. A cue whose tempo only changes through `TempoChangeEvent`s comes back equal,
+/// but MIDI has no gradual tempo changes, so a `GradualTempoChangeEvent` comes back as a
+/// `TempoChangeEvent` on every beat of the change. Time signature groupings and all other events
+/// are not kept.
+pub fn write_cue(cue: &Cue, options: &SmfExport) -> Vec<u8> {
+    let ppq = options.ppq.max(1) as u64;
+    let mut conductor = TrackWriter::new();
+    let mut click = TrackWriter::new();
+    if !cue.metadata.name.is_empty() {
+        conductor.meta(0, META_TRACK_NAME, cue.metadata.name.as_bytes());
+    }
+    let mut time_signature = TimeSignature::default();
+    let mut tempo = 0;
+    let mut tick = 0;
+    for (idx, beat) in cue.beats.iter().enumerate() {
+        match beat.time_signature() {
+            Some(signature) => time_signature = signature,
+            None if idx > 0 => {}
+            None => time_signature = TimeSignature::default(),
+        }
+        if idx == 0 || beat.time_signature().is_some() {
+            conductor.meta(
+                tick,
+                META_TIME_SIGNATURE,
+                &[
+                    time_signature.numerator,
+                    time_signature.denominator.max(1).ilog2() as u8,
+                    24,
+                    8,
+                ],
+            );
+        }
+        let denominator = 1u64 << time_signature.denominator.max(1).ilog2();
+        let beat_tempo = (beat.length as u64 * denominator / 4).clamp(1, 0xFFFFFF) as u32;
+        if beat_tempo != tempo {
+            tempo = beat_tempo;
+            conductor.meta(tick, META_TEMPO, &tempo.to_be_bytes()[1..]);
+        }
+        for event in &beat.events {
+            if let BeatEvent::RehearsalMarkEvent { label } = event {
+                conductor.meta(tick, META_MARKER, label.as_bytes());
+            }
+        }
+
+        let beat_ticks = (ppq * 4 / denominator).max(1);
+        if let Some(notes) = &options.click {
+            let (note, velocity) = if beat.count == 1 {
+                (notes.accent_note, notes.accent_velocity)
+            } else {
+                (notes.note, notes.velocity)
+            };
+            let channel = notes.channel & 0x0F;
+            click.event(tick, &[0x90 | channel, note & 0x7F, velocity & 0x7F]);
+            click.event(tick + beat_ticks / 2, &[0x80 | channel, note & 0x7F, 0]);
+        }
+        tick += beat_ticks;
+    }
+
+    let tracks: u16 = if options.click.is_some() { 2 } else { 1 };
+    let mut header = 1u16.to_be_bytes().to_vec();
+    header.extend(tracks.to_be_bytes());
+    header.extend((ppq as u16).to_be_bytes());
+    let mut out = vec![];
+    write_chunk(&mut out, b"MThd", &header);
+    conductor.finish(tick, &mut out);
+    if options.click.is_some() {
+        click.finish(tick, &mut out);
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cue::TempoCurve;
+
+    fn chunk(kind: &[u8], data: &[u8]) -> Vec<u8> {
+        let mut bytes = vec![];
+        write_chunk(&mut bytes, kind, data);
+        bytes
+    }
+
+    fn file(format: u16, tracks: &[Vec<u8>]) -> Vec<u8> {
+        let mut header = format.to_be_bytes().to_vec();
+        header.extend((tracks.len() as u16).to_be_bytes());
+        header.extend(480u16.to_be_bytes());
+        let mut bytes = chunk(b"MThd", &header);
+        for track in tracks {
+            bytes.extend(chunk(b"MTrk", track));
+        }
+        bytes
+    }
+
+    /// Track from (delta, event bytes) pairs, ending with end of track.
+    fn track(events: &[(u32, &[u8])]) -> Vec<u8> {
+        let mut bytes = vec![];
+        for (delta, event) in events {
+            write_vlq(&mut bytes, *delta);
+            bytes.extend(*event);
+        }
+        bytes.extend([0, META, META_END_OF_TRACK, 0]);
+        bytes
+    }
+
+    #[test]
+    fn tempo_map() {
+        let conductor = track(&[
+            (0, &[META, META_TRACK_NAME, 4, b'S', b'o', b'n', b'g']),
+            (0, &[META, META_TEMPO, 3, 0x07, 0xA1, 0x20]),
+            (0, &[META, META_TIME_SIGNATURE, 4, 4, 2, 24, 8]),
+            (3840, &[META, META_MARKER, 5, b'V', b'e', b'r', b's', b'e']),
+            (0, &[META, META_TEMPO, 3, 0x06, 0x1A, 0x80]),
+            (1920, &[META, META_TIME_SIGNATURE, 4, 3, 2, 24, 8]),
+            (2400, &[META, META_CUE_POINT, 3, b'E', b'n', b'd']),
+            (480, &[META, META_MARKER, 3, b'O', b'u', b't']),
+        ]);
+        // Notes with running status and a program change.
+        let notes = track(&[
+            (0, &[0xC0, 5]),
+            (0, &[0x90, 60, 100]),
+            (480, &[60, 0]),
+            (8160, &[0x80, 60, 0]),
+        ]);
+        let cue = read_cue(&file(1, &[conductor, notes])).unwrap();
+        assert_eq!(cue.metadata.name, "Song");
+        assert_eq!(cue.beats.len(), 18);
+        assert!(cue.beats[..8].iter().all(|b| b.length == 500000));
+        assert!(cue.beats[8..].iter().all(|b| b.length == 400000));
+        assert!(
+            cue.beats[8].events
+                == [
+                    BeatEvent::TempoChangeEvent { tempo: 150 },
+                    BeatEvent::RehearsalMarkEvent {
+                        label: "Verse".to_string()
+                    },
+                ]
+        );
+        assert_eq!((cue.beats[8].bar_number, cue.beats[8].count), (3, 1));
+        assert_eq!((cue.beats[12].bar_number, cue.beats[12].count), (4, 1));
+        assert_eq!((cue.beats[14].bar_number, cue.beats[14].count), (4, 3));
+        assert_eq!((cue.beats[15].bar_number, cue.beats[15].count), (5, 1));
+        assert_eq!(cue.time_signature_at(17).to_string(), "3/4");
+        assert!(matches!(
+            &cue.beats[17].events[..],
+            [
+                BeatEvent::RehearsalMarkEvent { label: end },
+                BeatEvent::RehearsalMarkEvent { label: out },
+            ] if end == "End" && out == "Out"
+        ));
+
+        // Whole BPM tempos changing on a beat survive recalculation.
+        let mut recalculated = cue.clone();
+        recalculated.recalculate_tempo_changes();
+        assert_eq!(recalculated, cue);
+    }
+
+    #[test]
+    fn tempo_within_beat() {
+        // Quarter at 120 bpm, halved in tempo halfway through the first beat of 6/8.
+        let conductor = track(&[
+            (0, &[META, META_TIME_SIGNATURE, 4, 6, 3, 24, 8]),
+            (120, &[META, META_TEMPO, 3, 0x0F, 0x42, 0x40]),
+            (1320, &[META, META_MARKER, 0]),
+        ]);
+        let cue = read_cue(&file(0, &[conductor])).unwrap();
+        assert_eq!(cue.beats.len(), 6);
+        assert_eq!(cue.beats[0].length, 125000 + 250000);
+        assert_eq!(cue.beats[1].length, 500000);
+        assert!(cue.beats[0].events[1] == BeatEvent::TempoChangeEvent { tempo: 120 });
+        assert_eq!(cue.beats[5].count, 6);
+
+        // The change within the first beat can't be expressed in BPM, so recalculating loses it.
+        let mut recalculated = cue.clone();
+        recalculated.recalculate_tempo_changes();
+        assert_eq!(recalculated.beats[0].length, 500000);
+        assert_eq!(recalculated.beats[1..], cue.beats[1..]);
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!(read_cue(b"RIFF"), Err(SmfError::InvalidHeader));
+        assert_eq!(read_cue(&file(2, &[])), Err(SmfError::UnsupportedFormat(2)));
+        let mut smpte = file(0, &[]);
+        smpte[12] = 0xE7;
+        assert_eq!(read_cue(&smpte), Err(SmfError::SmpteDivision));
+        let mut truncated = file(0, &[track(&[(0, &[0x90, 60, 100])])]);
+        truncated.truncate(truncated.len() - 6);
+        assert_eq!(read_cue(&truncated), Err(SmfError::UnexpectedEnd));
+        assert_eq!(
+            read_cue(&file(0, &[track(&[(0, &[60, 100])])])),
+            Err(SmfError::InvalidStatus(60))
+        );
+    }
+
+    fn song() -> Cue {
+        let mut cue = Cue::empty();
+        cue.metadata.name = "Song".to_string();
+        cue.beats = (0..20).map(|_| Beat::empty()).collect();
+        cue.beats[0].events = vec![
+            BeatEvent::TimeSignatureEvent {
+                numerator: 4,
+                denominator: 4,
+                grouping: None,
+            },
+            BeatEvent::TempoChangeEvent { tempo: 120 },
+        ];
+        cue.beats[8].events = vec![
+            BeatEvent::TempoChangeEvent { tempo: 150 },
+            BeatEvent::RehearsalMarkEvent {
+                label: "Chorus".to_string(),
+            },
+        ];
+        cue.beats[12].events = vec![
+            BeatEvent::TimeSignatureEvent {
+                numerator: 6,
+                denominator: 8,
+                grouping: None,
+            },
+            BeatEvent::TempoChangeEvent { tempo: 240 },
+        ];
+        cue.recalculate_tempo_changes();
+        cue.reorder_numbers();
+        cue
+    }
+
+    #[test]
+    fn round_trip() {
+        let cue = song();
+        let bytes = write_cue(&cue, &SmfExport::default());
+        assert_eq!(read_cue(&bytes).unwrap(), cue);
+
+        let with_click = SmfExport {
+            click: Some(ClickNotes::default()),
+            ..SmfExport::default()
+        };
+        assert_eq!(read_cue(&write_cue(&cue, &with_click)).unwrap(), cue);
+    }
+
+    #[test]
+    fn round_trip_ramp() {
+        let mut cue = song();
+        cue.beats[4]
+            .events
+            .push(BeatEvent::GradualTempoChangeEvent {
+                start_tempo: 120,
+                end_tempo: 150,
+                length: 4,
+                curve: TempoCurve::LinearTempo,
+            });
+        cue.recalculate_tempo_changes();
+        let read = read_cue(&write_cue(&cue, &SmfExport::default())).unwrap();
+        assert_ne!(read, cue);
+        let lengths = |cue: &Cue| cue.beats.iter().map(|b| b.length).collect::<Vec<_>>();
+        assert_eq!(lengths(&read), lengths(&cue));
+        assert_eq!(read.beats[5].length, 470588);
+        // The ramp starts at the tempo already in effect, so its first beat needs no event.
+        assert!(read.beats[4].events.is_empty());
+        for (idx, tempo) in [(5, 128), (6, 135), (7, 142), (8, 150)] {
+            assert!(
+                read.beats[idx].events[..1] == [BeatEvent::TempoChangeEvent { tempo }],
+                "beat {idx}"
+            );
+        }
+        for (read, beat) in read.beats.iter().zip(&cue.beats) {
+            assert_eq!((read.bar_number, read.count), (beat.bar_number, beat.count));
+        }
+    }
+
+    #[test]
+    fn click_track() {
+        let bytes = write_cue(
+            &song(),
+            &SmfExport {
+                click: Some(ClickNotes::default()),
+                ..SmfExport::default()
+            },
+        );
+        assert_eq!(u16::from_be_bytes([bytes[10], bytes[11]]), 2);
+        let conductor_len = u32::from_be_bytes(bytes[18..22].try_into().unwrap()) as usize;
+        let click = &bytes[22 + conductor_len..];
+        assert_eq!(&click[..4], b"MTrk");
+        assert_eq!(
+            click[8..24],
+            [
+                0x00, 0x99, 76, 127, // Count 1 accented
+                0x81, 0x70, 0x89, 76, 0, // Off after 240 ticks
+                0x81, 0x70, 0x99, 77, 100, // Count 2
+                0x81, 0x70,
+            ]
+        );
+    }
+}
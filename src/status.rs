@@ -2,10 +2,10 @@ use serde::{Deserialize, Serialize};
 
 use crate::{
     config::SystemConfiguration,
-    cue::{Beat, Cue, JumpModeChange},
+    cue::{AccentLevel, Beat, Cue, JumpModeChange, RepeatCounters},
     network::{Heartbeat, JACKStatus, NetworkStatus},
     show::Show,
-    timecode::TimecodeInstant,
+    timecode::{SyncStatus, TimecodeInstant, TimecodePlayrateMode},
 };
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -22,6 +22,15 @@ pub struct BeatState {
     pub next_beat_idx: usize,
     pub beat: Beat,
     pub requested_vlt_action: JumpModeChange,
+    /// Clicks to sound over the current beat, see `Cue::click_pattern`.
+    #[serde(default)]
+    pub click_pattern: Vec<AccentLevel>,
+    /// Microseconds after the start of the beat that each of its events fires, see
+    /// `Beat::event_offsets_us`.
+    #[serde(default)]
+    pub event_offsets_us: Vec<u64>,
+    #[serde(default)]
+    pub repeat_counters: RepeatCounters,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize, Default)]
@@ -43,7 +52,11 @@ pub struct TransportState {
     pub running: bool,
     pub vlt: bool,
     pub ltc: TimecodeInstant,
+    #[serde(default)]
+    pub ltc_sync: SyncStatus,
     pub playrate_percent: usize,
+    #[serde(default)]
+    pub timecode_playrate_mode: TimecodePlayrateMode,
 }
 
 impl Default for TransportState {
@@ -53,7 +66,9 @@ impl Default for TransportState {
             running: false,
             vlt: false,
             ltc: TimecodeInstant::default(),
+            ltc_sync: SyncStatus::default(),
             playrate_percent: 100,
+            timecode_playrate_mode: TimecodePlayrateMode::default(),
         }
     }
 }
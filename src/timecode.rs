@@ -1,40 +1,584 @@
-use serde::{Deserialize, Serialize};
-use std::fmt::Display;
+use serde::{
+    Deserialize, Deserializer, Serialize, Serializer,
+    de::{self, Visitor},
+};
+use std::{
+    cmp::Ordering,
+    error::Error,
+    fmt::{self, Display},
+    ops::{Add, AddAssign, Neg, Sub, SubAssign},
+    str::FromStr,
+};
+
+/// SMPTE frame rates. Drop-frame rates count frames at the nominal rate but skip frame numbers 0
+/// and 1 (0..=3 at 59.94) at the start of every minute except every tenth.
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum FrameRate {
+    Fps23_976,
+    Fps24,
+    #[default]
+    Fps25,
+    Fps29_97,
+    Fps29_97Df,
+    Fps30,
+    Fps47_95,
+    Fps48,
+    Fps50,
+    Fps59_94,
+    Fps59_94Df,
+    Fps60,
+}
+
+impl FrameRate {
+    pub const ALL: [FrameRate; 12] = [
+        FrameRate::Fps23_976,
+        FrameRate::Fps24,
+        FrameRate::Fps25,
+        FrameRate::Fps29_97,
+        FrameRate::Fps29_97Df,
+        FrameRate::Fps30,
+        FrameRate::Fps47_95,
+        FrameRate::Fps48,
+        FrameRate::Fps50,
+        FrameRate::Fps59_94,
+        FrameRate::Fps59_94Df,
+        FrameRate::Fps60,
+    ];
+
+    /// Frame rate stored as a plain integer by older show files.
+    pub fn from_nominal(frame_rate: usize) -> Option<FrameRate> {
+        match frame_rate {
+            24 => Some(FrameRate::Fps24),
+            25 => Some(FrameRate::Fps25),
+            30 => Some(FrameRate::Fps30),
+            48 => Some(FrameRate::Fps48),
+            50 => Some(FrameRate::Fps50),
+            60 => Some(FrameRate::Fps60),
+            _ => None,
+        }
+    }
+
+    /// Number of frame labels per second, i.e. the frame field wraps at this value.
+    pub fn nominal(&self) -> usize {
+        match self {
+            FrameRate::Fps23_976 | FrameRate::Fps24 => 24,
+            FrameRate::Fps25 => 25,
+            FrameRate::Fps29_97 | FrameRate::Fps29_97Df | FrameRate::Fps30 => 30,
+            FrameRate::Fps47_95 | FrameRate::Fps48 => 48,
+            FrameRate::Fps50 => 50,
+            FrameRate::Fps59_94 | FrameRate::Fps59_94Df | FrameRate::Fps60 => 60,
+        }
+    }
+
+    pub fn is_drop_frame(&self) -> bool {
+        matches!(self, FrameRate::Fps29_97Df | FrameRate::Fps59_94Df)
+    }
+
+    /// Whether the rate runs at 1000/1001 of its nominal rate.
+    pub fn is_pulldown(&self) -> bool {
+        matches!(
+            self,
+            FrameRate::Fps23_976
+                | FrameRate::Fps29_97
+                | FrameRate::Fps29_97Df
+                | FrameRate::Fps47_95
+                | FrameRate::Fps59_94
+                | FrameRate::Fps59_94Df
+        )
+    }
+
+    /// Exact frames per second as (numerator, denominator).
+    pub fn frames_per_second(&self) -> (u64, u64) {
+        if self.is_pulldown() {
+            (self.nominal() as u64 * 1000, 1001)
+        } else {
+            (self.nominal() as u64, 1)
+        }
+    }
+
+    /// Exact duration of one frame in seconds as (numerator, denominator).
+    pub fn frame_duration(&self) -> (u64, u64) {
+        let (num, den) = self.frames_per_second();
+        (den, num)
+    }
+
+    pub fn as_f64(&self) -> f64 {
+        let (num, den) = self.frames_per_second();
+        num as f64 / den as f64
+    }
+
+    pub fn dropped_frames_per_minute(&self) -> usize {
+        if self.is_drop_frame() {
+            self.nominal() / 15
+        } else {
+            0
+        }
+    }
+
+    /// Length of the 24 hour timecode day in frames.
+    pub fn frames_per_day(&self) -> i64 {
+        (self.nominal() * 86400 - self.dropped_frames_per_minute() * (1440 - 144)) as i64
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            FrameRate::Fps23_976 => "Fps23_976",
+            FrameRate::Fps24 => "Fps24",
+            FrameRate::Fps25 => "Fps25",
+            FrameRate::Fps29_97 => "Fps29_97",
+            FrameRate::Fps29_97Df => "Fps29_97Df",
+            FrameRate::Fps30 => "Fps30",
+            FrameRate::Fps47_95 => "Fps47_95",
+            FrameRate::Fps48 => "Fps48",
+            FrameRate::Fps50 => "Fps50",
+            FrameRate::Fps59_94 => "Fps59_94",
+            FrameRate::Fps59_94Df => "Fps59_94Df",
+            FrameRate::Fps60 => "Fps60",
+        }
+    }
+}
+
+impl Display for FrameRate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FrameRate::Fps23_976 => write!(f, "23.976"),
+            FrameRate::Fps24 => write!(f, "24"),
+            FrameRate::Fps25 => write!(f, "25"),
+            FrameRate::Fps29_97 => write!(f, "29.97"),
+            FrameRate::Fps29_97Df => write!(f, "29.97 DF"),
+            FrameRate::Fps30 => write!(f, "30"),
+            FrameRate::Fps47_95 => write!(f, "47.95"),
+            FrameRate::Fps48 => write!(f, "48"),
+            FrameRate::Fps50 => write!(f, "50"),
+            FrameRate::Fps59_94 => write!(f, "59.94"),
+            FrameRate::Fps59_94Df => write!(f, "59.94 DF"),
+            FrameRate::Fps60 => write!(f, "60"),
+        }
+    }
+}
+
+impl Serialize for FrameRate {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_unit_variant("FrameRate", *self as u32, self.name())
+    }
+}
+
+struct FrameRateVisitor;
+
+impl Visitor<'_> for FrameRateVisitor {
+    type Value = FrameRate;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "a frame rate name or an integer frame rate")
+    }
+
+    fn visit_u64<E: de::Error>(self, v: u64) -> Result<FrameRate, E> {
+        // Older versions wrote 0 for a default timecode instant.
+        if v == 0 {
+            return Ok(FrameRate::default());
+        }
+        FrameRate::from_nominal(v as usize)
+            .ok_or_else(|| E::custom(format!("unsupported frame rate {v}")))
+    }
+
+    fn visit_i64<E: de::Error>(self, v: i64) -> Result<FrameRate, E> {
+        match u64::try_from(v) {
+            Ok(v) => self.visit_u64(v),
+            Err(_) => Err(E::custom(format!("unsupported frame rate {v}"))),
+        }
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<FrameRate, E> {
+        FrameRate::ALL
+            .into_iter()
+            .find(|rate| rate.name() == v)
+            .ok_or_else(|| E::unknown_variant(v, &[]))
+    }
+}
+
+/// Accepts the variant name, or the plain integer written by older show files, where 0 stands for
+/// the default rate.
+impl<'de> Deserialize<'de> for FrameRate {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<FrameRate, D::Error> {
+        deserializer.deserialize_any(FrameRateVisitor)
+    }
+}
+
+/// How generated timecode behaves when the transport plays at other than 100%.
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum TimecodePlayrateMode {
+    /// Run at the playrate, so that timecode stays aligned with the beats.
+    FollowMusicalTime,
+    /// Run at nominal speed regardless of playrate.
+    #[default]
+    FollowWallClock,
+    /// Hold the current timecode while the playrate is varied.
+    Freeze,
+}
+
+/// Direction in which received timecode is running.
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TimecodeDirection {
+    #[default]
+    Forward,
+    Reverse,
+}
+
+/// How the user bits are to be read, signalled by binary group flags BGF0 and BGF2.
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum UserBitsFormat {
+    #[default]
+    Unspecified,
+    /// Four eight-bit characters.
+    Characters,
+    /// Date and time zone as in SMPTE 309.
+    Date,
+    PageLine,
+}
+
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct UserBitsDate {
+    /// Two digit year.
+    pub year: u8,
+    pub month: u8,
+    pub day: u8,
+    /// SMPTE 309 time zone code.
+    pub time_zone: u8,
+}
+
+/// The 32 user bits carried alongside timecode, with the binary group flags describing them.
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct UserBits {
+    /// Group 1 in the lowest four bits through group 8 in the highest.
+    pub bits: u32,
+    pub format: UserBitsFormat,
+    /// BGF1, set when the timecode is locked to an external clock.
+    pub clock_flag: bool,
+}
+
+impl UserBits {
+    pub fn raw(bits: u32) -> UserBits {
+        UserBits {
+            bits,
+            ..Default::default()
+        }
+    }
+
+    /// Character n goes in groups 2n-1 (low nibble) and 2n (high nibble).
+    pub fn from_chars(chars: [u8; 4]) -> UserBits {
+        UserBits {
+            bits: u32::from_le_bytes(chars),
+            format: UserBitsFormat::Characters,
+            ..Default::default()
+        }
+    }
+
+    pub fn chars(&self) -> [u8; 4] {
+        self.bits.to_le_bytes()
+    }
+
+    /// Day in groups 1-2, month in 3-4, year in 5-6 and time zone in 7-8, all BCD except the
+    /// time zone code.
+    pub fn from_date(date: UserBitsDate) -> UserBits {
+        let bcd = |value: u8| ((value / 10 % 10) << 4) | (value % 10);
+        UserBits {
+            bits: u32::from_le_bytes([
+                bcd(date.day),
+                bcd(date.month),
+                bcd(date.year),
+                date.time_zone,
+            ]),
+            format: UserBitsFormat::Date,
+            ..Default::default()
+        }
+    }
+
+    /// Reads the bits as a date, if they hold a valid one.
+    pub fn date(&self) -> Option<UserBitsDate> {
+        let from_bcd = |value: u8| {
+            if value & 0xF > 9 || value >> 4 > 9 {
+                None
+            } else {
+                Some((value >> 4) * 10 + (value & 0xF))
+            }
+        };
+        let [day, month, year, time_zone] = self.bits.to_le_bytes();
+        let date = UserBitsDate {
+            year: from_bcd(year)?,
+            month: from_bcd(month)?,
+            day: from_bcd(day)?,
+            time_zone,
+        };
+        if (1..=12).contains(&date.month) && (1..=31).contains(&date.day) {
+            Some(date)
+        } else {
+            None
+        }
+    }
+
+    /// Four-bit group, 1 to 8. Groups outside that range read as 0.
+    pub fn group(&self, group: usize) -> u8 {
+        match group {
+            1..=8 => (self.bits >> ((group - 1) * 4)) as u8 & 0xF,
+            _ => 0,
+        }
+    }
+
+    /// Binary group flags as `BGF2 BGF1 BGF0` in the three low bits.
+    pub fn binary_group_flags(&self) -> u8 {
+        let format = match self.format {
+            UserBitsFormat::Unspecified => 0b000,
+            UserBitsFormat::Characters => 0b001,
+            UserBitsFormat::Date => 0b100,
+            UserBitsFormat::PageLine => 0b101,
+        };
+        format | (self.clock_flag as u8) << 1
+    }
+
+    pub fn from_binary_group_flags(bits: u32, flags: u8) -> UserBits {
+        UserBits {
+            bits,
+            format: match flags & 0b101 {
+                0b000 => UserBitsFormat::Unspecified,
+                0b001 => UserBitsFormat::Characters,
+                0b100 => UserBitsFormat::Date,
+                _ => UserBitsFormat::PageLine,
+            },
+            clock_flag: flags & 0b010 != 0,
+        }
+    }
+}
 
 #[derive(Default, Debug, Clone, Serialize, Deserialize)]
 pub struct TimecodeInstant {
-    pub frame_rate: usize,
+    pub frame_rate: FrameRate,
     pub h: i16,
     pub m: i16,
     pub s: i16,
     pub f: i16,
     pub frame_progress: u16,
+    #[serde(default)]
+    pub user_bits: UserBits,
+    /// Remainder below one `frame_progress` step, kept so that many small `add_us` calls do not
+    /// drift from wall clock.
+    #[serde(skip)]
+    pub progress_remainder: u64,
 }
 
+const PROGRESS_PER_FRAME: i128 = 65536;
+const US_PER_SECOND: i128 = 1000000;
+
 impl PartialEq for TimecodeInstant {
     fn eq(&self, other: &TimecodeInstant) -> bool {
         self.f == other.f && self.s == other.s && self.m == other.m && self.h == other.h
     }
 }
 
+impl Eq for TimecodeInstant {}
+
+impl PartialOrd for TimecodeInstant {
+    fn partial_cmp(&self, other: &TimecodeInstant) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for TimecodeInstant {
+    fn cmp(&self, other: &TimecodeInstant) -> Ordering {
+        (self.h, self.m, self.s, self.f).cmp(&(other.h, other.m, other.s, other.f))
+    }
+}
+
 impl Display for TimecodeInstant {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(
             f,
-            "{:0>2}:{:0>2}:{:0>2}:{:0>2}",
-            self.h, self.m, self.s, self.f
+            "{:0>2}:{:0>2}:{:0>2}{}{:0>2}",
+            self.h,
+            self.m,
+            self.s,
+            if self.frame_rate.is_drop_frame() {
+                ';'
+            } else {
+                ':'
+            },
+            self.f
         )
     }
 }
 
+#[derive(Debug, Clone, PartialEq)]
+pub enum TimecodeParseError {
+    /// Not four fields separated by `:`, `;` or `.`.
+    InvalidFormat,
+    InvalidNumber {
+        field: &'static str,
+        value: String,
+    },
+    OutOfRange {
+        field: &'static str,
+        value: usize,
+        limit: usize,
+    },
+    /// A `;` separator was given for a rate that does not drop frames.
+    NotDropFrame(FrameRate),
+    /// The frame number is skipped by drop-frame counting.
+    DroppedFrame,
+}
+
+impl Display for TimecodeParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TimecodeParseError::InvalidFormat => {
+                write!(f, "Expected HH:MM:SS:FF, HH:MM:SS;FF or HH:MM:SS.FF")
+            }
+            TimecodeParseError::InvalidNumber { field, value } => {
+                write!(f, "{field} \"{value}\" is not a number")
+            }
+            TimecodeParseError::OutOfRange {
+                field,
+                value,
+                limit,
+            } => write!(f, "{field} {value} is out of range, must be below {limit}"),
+            TimecodeParseError::NotDropFrame(rate) => {
+                write!(
+                    f,
+                    "; marks drop-frame timecode but {rate} fps does not drop frames"
+                )
+            }
+            TimecodeParseError::DroppedFrame => {
+                write!(f, "Frame does not exist in drop-frame timecode")
+            }
+        }
+    }
+}
+
+impl Error for TimecodeParseError {}
+
+/// Parses `HH:MM:SS;FF` as 29.97 drop-frame and anything else as 25 fps, the default frame rate.
+/// A string alone can't say which other rate it is in, so `"00:00:00:29"` fails with `OutOfRange`
+/// here even though it is valid at 30 fps. Use `TimecodeInstant::parse` wherever the rate is
+/// known, which it is for operator input and show files.
+impl FromStr for TimecodeInstant {
+    type Err = TimecodeParseError;
+    fn from_str(s: &str) -> Result<TimecodeInstant, TimecodeParseError> {
+        let frame_rate = if s.contains(';') {
+            FrameRate::Fps29_97Df
+        } else {
+            FrameRate::default()
+        };
+        TimecodeInstant::parse(s, frame_rate)
+    }
+}
+
 impl TimecodeInstant {
-    pub fn new(frame_rate: usize) -> TimecodeInstant {
+    pub fn new(frame_rate: FrameRate) -> TimecodeInstant {
         TimecodeInstant {
             frame_rate,
             ..Default::default()
         }
     }
 
+    pub fn parse(s: &str, frame_rate: FrameRate) -> Result<TimecodeInstant, TimecodeParseError> {
+        let s = s.trim();
+        let Some(frame_separator) = s.rfind([':', ';', '.']) else {
+            return Err(TimecodeParseError::InvalidFormat);
+        };
+        let mut fields: Vec<&str> = s[..frame_separator].split(':').collect();
+        fields.push(&s[frame_separator + 1..]);
+        if fields.len() != 4 {
+            return Err(TimecodeParseError::InvalidFormat);
+        }
+        if s.as_bytes()[frame_separator] == b';' && !frame_rate.is_drop_frame() {
+            return Err(TimecodeParseError::NotDropFrame(frame_rate));
+        }
+
+        let mut values = [0; 4];
+        for (idx, (field, limit)) in ["Hours", "Minutes", "Seconds", "Frames"]
+            .into_iter()
+            .zip([24, 60, 60, frame_rate.nominal()])
+            .enumerate()
+        {
+            let text = fields[idx];
+            if text.is_empty() || !text.bytes().all(|b| b.is_ascii_digit()) {
+                return Err(TimecodeParseError::InvalidNumber {
+                    field,
+                    value: text.to_string(),
+                });
+            }
+            let value = text.parse().unwrap_or(usize::MAX);
+            if value >= limit {
+                return Err(TimecodeParseError::OutOfRange {
+                    field,
+                    value,
+                    limit,
+                });
+            }
+            values[idx] = value;
+        }
+        let [h, m, s, f] = values;
+        if m % 10 != 0 && s == 0 && f < frame_rate.dropped_frames_per_minute() {
+            return Err(TimecodeParseError::DroppedFrame);
+        }
+
+        let mut time = TimecodeInstant::new(frame_rate);
+        time.set_time(h, m, s, f);
+        Ok(time)
+    }
+
+    pub fn from_frame_count(frame_rate: FrameRate, frames: i64) -> TimecodeInstant {
+        let mut time = TimecodeInstant::new(frame_rate);
+        time.set_frame_count(frames);
+        time
+    }
+
+    pub fn from_seconds(frame_rate: FrameRate, seconds: f64) -> TimecodeInstant {
+        let progress = (seconds * frame_rate.as_f64() * PROGRESS_PER_FRAME as f64).round() as i64;
+        let mut time = TimecodeInstant::new(frame_rate);
+        time.frame_progress = progress.rem_euclid(PROGRESS_PER_FRAME as i64) as u16;
+        time.set_frame_count(progress.div_euclid(PROGRESS_PER_FRAME as i64));
+        time
+    }
+
+    pub fn from_samples(
+        frame_rate: FrameRate,
+        samples: u64,
+        sample_rate: usize,
+    ) -> TimecodeInstant {
+        let (num, den) = frame_rate.frames_per_second();
+        let progress = samples as i128 * num as i128 * PROGRESS_PER_FRAME
+            / (sample_rate as i128 * den as i128);
+        let mut time = TimecodeInstant::new(frame_rate);
+        time.frame_progress = progress.rem_euclid(PROGRESS_PER_FRAME) as u16;
+        time.set_frame_count(progress.div_euclid(PROGRESS_PER_FRAME) as i64);
+        time
+    }
+
+    /// Seconds since 00:00:00:00, including `frame_progress`.
+    pub fn to_seconds(&self) -> f64 {
+        let (num, den) = self.frame_rate.frames_per_second();
+        (self.frame_count() as f64 + self.frame_progress as f64 / PROGRESS_PER_FRAME as f64)
+            * den as f64
+            / num as f64
+    }
+
+    /// First sample at or after this position, counting from 00:00:00:00 at `sample_rate`.
+    pub fn to_samples(&self, sample_rate: usize) -> u64 {
+        let (num, den) = self.frame_rate.frames_per_second();
+        let progress =
+            self.frame_count() as i128 * PROGRESS_PER_FRAME + self.frame_progress as i128;
+        let divisor = num as i128 * PROGRESS_PER_FRAME;
+        ((progress * sample_rate as i128 * den as i128 + divisor - 1) / divisor) as u64
+    }
+
+    /// Distance forward to `other`, going past midnight if `other` is earlier in the day.
+    pub fn until(&self, other: &TimecodeInstant) -> TimecodeDuration {
+        let duration = other - self;
+        TimecodeDuration {
+            frames: duration.frames.rem_euclid(self.frame_rate.frames_per_day()),
+            ..duration
+        }
+    }
+
     pub fn add_progress(&mut self, progress: u16) {
         let prog_of = self.frame_progress as u32 + progress as u32;
         self.frame_progress = (prog_of % 65536) as u16;
@@ -44,12 +588,25 @@ impl TimecodeInstant {
         self.propagate();
     }
     pub fn add_us(&mut self, time_us: u64) {
-        self.f += (time_us * self.frame_rate as u64 / 1000000) as i16;
-        self.propagate();
+        self.advance_us(time_us as i128);
     }
     pub fn sub_us(&mut self, time_us: u64) {
-        self.f -= (time_us * self.frame_rate as u64 / 1000000) as i16;
-        self.propagate();
+        self.advance_us(-(time_us as i128));
+    }
+
+    /// Advances by `time_us` of wall clock while the transport plays at `playrate_percent`.
+    pub fn advance(&mut self, time_us: u64, playrate_percent: usize, mode: TimecodePlayrateMode) {
+        match mode {
+            TimecodePlayrateMode::FollowMusicalTime => {
+                self.advance_scaled_us(time_us as i128, playrate_percent as i128)
+            }
+            TimecodePlayrateMode::FollowWallClock => self.add_us(time_us),
+            TimecodePlayrateMode::Freeze => {
+                if playrate_percent == 100 {
+                    self.add_us(time_us)
+                }
+            }
+        }
     }
 
     pub fn set_time(&mut self, h: usize, m: usize, s: usize, f: usize) {
@@ -59,15 +616,365 @@ impl TimecodeInstant {
         self.f = f as i16;
     }
 
+    /// Number of frames elapsed since 00:00:00:00.
+    pub fn frame_count(&self) -> i64 {
+        let minutes = self.h as i64 * 60 + self.m as i64;
+        (minutes * 60 + self.s as i64) * self.frame_rate.nominal() as i64 + self.f as i64
+            - self.frame_rate.dropped_frames_per_minute() as i64
+                * (minutes - minutes.div_euclid(10))
+    }
+
+    /// Sets the time from a frame count, wrapping at 24 hours. `frame_progress` is kept.
+    pub fn set_frame_count(&mut self, frame: i64) {
+        let mut frame = frame.rem_euclid(self.frame_rate.frames_per_day());
+        let fps = self.frame_rate.nominal() as i64;
+        let drop = self.frame_rate.dropped_frames_per_minute() as i64;
+        if drop > 0 {
+            let frames_per_minute = fps * 60 - drop;
+            let frames_per_ten_minutes = fps * 600 - drop * 9;
+            let tens = frame.div_euclid(frames_per_ten_minutes);
+            let rest = frame.rem_euclid(frames_per_ten_minutes);
+            frame += drop * 9 * tens;
+            if rest > drop {
+                frame += drop * ((rest - drop) / frames_per_minute);
+            }
+        }
+        let seconds = frame / fps;
+        let minutes = seconds / 60;
+        self.f = (frame % fps) as i16;
+        self.s = (seconds % 60) as i16;
+        self.m = (minutes % 60) as i16;
+        self.h = (minutes / 60) as i16;
+    }
+
+    fn advance_us(&mut self, time_us: i128) {
+        self.advance_scaled_us(time_us, 100);
+    }
+
+    /// Advances by `time_us` running at `percent` of nominal speed.
+    fn advance_scaled_us(&mut self, time_us: i128, percent: i128) {
+        let (num, den) = self.frame_rate.frames_per_second();
+        let (num, den) = (num as i128, den as i128);
+        let divisor = den * US_PER_SECOND * 100;
+        let position = (self.frame_count() as i128 * PROGRESS_PER_FRAME
+            + self.frame_progress as i128)
+            * divisor
+            + self.progress_remainder as i128
+            + time_us * percent * num * PROGRESS_PER_FRAME;
+        let progress = position.div_euclid(divisor);
+        self.progress_remainder = position.rem_euclid(divisor) as u64;
+        self.frame_progress = progress.rem_euclid(PROGRESS_PER_FRAME) as u16;
+        self.set_frame_count(progress.div_euclid(PROGRESS_PER_FRAME) as i64);
+    }
+
     fn propagate(&mut self) {
-        self.s += self.f / self.frame_rate as i16;
-        self.f %= self.frame_rate as i16;
-        self.f += self.frame_rate as i16;
-        self.f %= self.frame_rate as i16;
-        self.m += self.s / 60;
-        self.s %= 60;
-        self.h += self.m / 60;
-        self.m %= 60;
+        self.set_frame_count(self.frame_count());
+    }
+}
+
+/// Converts a frame count between rates, rounding towards the earlier frame.
+fn convert_frames(frames: i64, from: FrameRate, to: FrameRate) -> i64 {
+    if from == to {
+        return frames;
+    }
+    let (from_num, from_den) = from.frames_per_second();
+    let (to_num, to_den) = to.frames_per_second();
+    (frames as i128 * (to_num * from_den) as i128).div_euclid((to_den * from_num) as i128) as i64
+}
+
+impl Add<TimecodeDuration> for TimecodeInstant {
+    type Output = TimecodeInstant;
+    fn add(mut self, rhs: TimecodeDuration) -> TimecodeInstant {
+        self += rhs;
+        self
+    }
+}
+
+impl AddAssign<TimecodeDuration> for TimecodeInstant {
+    fn add_assign(&mut self, rhs: TimecodeDuration) {
+        self.set_frame_count(self.frame_count() + rhs.frames_at(self.frame_rate));
+    }
+}
+
+impl Sub<TimecodeDuration> for TimecodeInstant {
+    type Output = TimecodeInstant;
+    fn sub(mut self, rhs: TimecodeDuration) -> TimecodeInstant {
+        self -= rhs;
+        self
+    }
+}
+
+impl SubAssign<TimecodeDuration> for TimecodeInstant {
+    fn sub_assign(&mut self, rhs: TimecodeDuration) {
+        *self += -rhs;
+    }
+}
+
+/// Signed difference in the frame rate of the left hand side. Does not wrap at midnight, see
+/// `TimecodeInstant::until` for that.
+impl Sub<&TimecodeInstant> for &TimecodeInstant {
+    type Output = TimecodeDuration;
+    fn sub(self, rhs: &TimecodeInstant) -> TimecodeDuration {
+        TimecodeDuration::new(
+            self.frame_rate,
+            self.frame_count() - convert_frames(rhs.frame_count(), rhs.frame_rate, self.frame_rate),
+        )
+    }
+}
+
+impl Sub for TimecodeInstant {
+    type Output = TimecodeDuration;
+    fn sub(self, rhs: TimecodeInstant) -> TimecodeDuration {
+        &self - &rhs
+    }
+}
+
+/// A signed number of frames at a given rate.
+#[derive(
+    Default, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize,
+)]
+pub struct TimecodeDuration {
+    pub frames: i64,
+    pub frame_rate: FrameRate,
+}
+
+impl TimecodeDuration {
+    pub fn new(frame_rate: FrameRate, frames: i64) -> TimecodeDuration {
+        TimecodeDuration { frames, frame_rate }
+    }
+
+    /// Whole frames elapsing in `time_us`, rounded towards zero.
+    pub fn from_us(frame_rate: FrameRate, time_us: i64) -> TimecodeDuration {
+        let (num, den) = frame_rate.frames_per_second();
+        TimecodeDuration::new(
+            frame_rate,
+            (time_us as i128 * num as i128 / (den as i128 * US_PER_SECOND)) as i64,
+        )
+    }
+
+    pub fn to_us(&self) -> i64 {
+        let (num, den) = self.frame_rate.frames_per_second();
+        (self.frames as i128 * den as i128 * US_PER_SECOND / num as i128) as i64
+    }
+
+    pub fn to_seconds(&self) -> f64 {
+        let (num, den) = self.frame_rate.frames_per_second();
+        self.frames as f64 * den as f64 / num as f64
+    }
+
+    pub fn frames_at(&self, frame_rate: FrameRate) -> i64 {
+        convert_frames(self.frames, self.frame_rate, frame_rate)
+    }
+}
+
+impl Display for TimecodeDuration {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.frames < 0 {
+            write!(f, "-")?;
+        }
+        // Durations don't wrap at midnight, so whole days are added to the hours.
+        let frames_per_day = self.frame_rate.frames_per_day();
+        let mut time = TimecodeInstant::new(self.frame_rate);
+        time.set_frame_count(self.frames.abs() % frames_per_day);
+        time.h += (self.frames.abs() / frames_per_day) as i16 * 24;
+        write!(f, "{time}")
+    }
+}
+
+impl Neg for TimecodeDuration {
+    type Output = TimecodeDuration;
+    fn neg(self) -> TimecodeDuration {
+        TimecodeDuration::new(self.frame_rate, -self.frames)
+    }
+}
+
+impl Add for TimecodeDuration {
+    type Output = TimecodeDuration;
+    fn add(self, rhs: TimecodeDuration) -> TimecodeDuration {
+        TimecodeDuration::new(
+            self.frame_rate,
+            self.frames + rhs.frames_at(self.frame_rate),
+        )
+    }
+}
+
+impl Sub for TimecodeDuration {
+    type Output = TimecodeDuration;
+    fn sub(self, rhs: TimecodeDuration) -> TimecodeDuration {
+        self + -rhs
+    }
+}
+
+/// How far a timecode follower trusts its source.
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum LockState {
+    #[default]
+    Unlocked,
+    /// Receiving timecode, but not yet enough contiguous frames to trust it.
+    Locking,
+    Locked,
+    /// Input has stopped and the position is running on the local clock.
+    Freewheeling,
+}
+
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum JamSyncMode {
+    /// Follow incoming timecode for as long as it arrives.
+    #[default]
+    Continuous,
+    /// Take the incoming position once on lock, then run on the local clock and ignore the input
+    /// from there on, freewheeling indefinitely.
+    OneShot,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SyncConfiguration {
+    pub jam_sync: JamSyncMode,
+    /// Contiguous frames needed before going from locking to locked.
+    pub lock_frames: usize,
+    /// Frames to keep running without input before giving up the lock.
+    pub freewheel_frames: usize,
+}
+
+impl Default for SyncConfiguration {
+    fn default() -> Self {
+        Self {
+            jam_sync: JamSyncMode::Continuous,
+            lock_frames: 4,
+            freewheel_frames: 25,
+        }
+    }
+}
+
+/// Snapshot of a `TimecodeSynchronizer` for reporting to clients.
+#[derive(Default, Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct SyncStatus {
+    pub state: LockState,
+    /// Smoothed offset of the source against the local clock since lock was gained, positive
+    /// when the source is ahead. A source running fast keeps on growing it, and a source that
+    /// starts arriving late holds it at the delay.
+    pub drift_us: i64,
+}
+
+/// Follows an external timecode source, fed with frames decoded from LTC or MTC and the local
+/// time at which they arrived. Frames lost on the cable do not break lock, as every frame is
+/// checked against the position predicted from the local clock rather than against the previous
+/// frame.
+#[derive(Debug, Clone, Default)]
+pub struct TimecodeSynchronizer {
+    pub config: SyncConfiguration,
+    pub state: LockState,
+    drift_us: f64,
+    /// Received position and the local time it applied at.
+    anchor: Option<(TimecodeInstant, u64)>,
+    /// First frame of the current lock and the local time it arrived, which drift is measured
+    /// from.
+    reference: Option<(TimecodeInstant, u64)>,
+    last_input_us: u64,
+    contiguous_frames: usize,
+}
+
+impl TimecodeSynchronizer {
+    pub fn new(config: SyncConfiguration) -> TimecodeSynchronizer {
+        TimecodeSynchronizer {
+            config,
+            ..Default::default()
+        }
+    }
+
+    pub fn feed(&mut self, time: &TimecodeInstant, now_us: u64) {
+        self.last_input_us = now_us;
+        let jammed = self.config.jam_sync == JamSyncMode::OneShot
+            && matches!(self.state, LockState::Locked | LockState::Freewheeling);
+        if jammed {
+            self.state = LockState::Locked;
+            return;
+        }
+
+        let error_us = self
+            .position(now_us)
+            .map(|predicted| Self::offset_us(&predicted, time));
+        match error_us {
+            Some(error_us) if error_us.abs() <= Self::frame_us(time.frame_rate) * 1.5 => {
+                self.contiguous_frames += 1;
+            }
+            _ => {
+                self.contiguous_frames = 1;
+                self.drift_us = 0.0;
+                self.reference = Some((time.clone(), now_us));
+            }
+        }
+        if let Some(reference) = &self.reference {
+            let offset_us = Self::offset_us(&Self::predict(reference, now_us), time);
+            self.drift_us = self.drift_us * 0.9 + offset_us * 0.1;
+        }
+        self.anchor = Some((time.clone(), now_us));
+        self.state = if self.contiguous_frames >= self.config.lock_frames {
+            LockState::Locked
+        } else {
+            LockState::Locking
+        };
+    }
+
+    /// Advances the state for time passing without input. Call regularly, e.g. once per period.
+    pub fn tick(&mut self, now_us: u64) -> LockState {
+        let Some((time, _)) = &self.anchor else {
+            return self.state;
+        };
+        let frame_us = Self::frame_us(time.frame_rate);
+        let silent_us = now_us.saturating_sub(self.last_input_us) as f64;
+        if silent_us <= frame_us * 2.0 {
+            return self.state;
+        }
+        let freewheel = self.config.jam_sync == JamSyncMode::OneShot
+            || silent_us <= frame_us * (self.config.freewheel_frames + 1) as f64;
+        self.state = match self.state {
+            LockState::Locked | LockState::Freewheeling if freewheel => LockState::Freewheeling,
+            _ => LockState::Unlocked,
+        };
+        if self.state == LockState::Unlocked {
+            self.anchor = None;
+            self.reference = None;
+            self.contiguous_frames = 0;
+        }
+        self.state
+    }
+
+    /// Estimated source position at local time `now_us`, or `None` when unlocked.
+    pub fn position(&self, now_us: u64) -> Option<TimecodeInstant> {
+        Some(Self::predict(self.anchor.as_ref()?, now_us))
+    }
+
+    pub fn status(&self) -> SyncStatus {
+        SyncStatus {
+            state: self.state,
+            drift_us: self.drift_us.round() as i64,
+        }
+    }
+
+    fn frame_us(frame_rate: FrameRate) -> f64 {
+        1000000.0 / frame_rate.as_f64()
+    }
+
+    /// Position at `now_us` of a source that was at `anchor` and has run at nominal speed since.
+    fn predict(anchor: &(TimecodeInstant, u64), now_us: u64) -> TimecodeInstant {
+        let (time, at_us) = anchor;
+        let mut time = time.clone();
+        time.add_us(now_us.saturating_sub(*at_us));
+        time
+    }
+
+    /// Signed distance from `from` to `to` in microseconds, taking the shorter way around midnight.
+    fn offset_us(from: &TimecodeInstant, to: &TimecodeInstant) -> f64 {
+        let (num, den) = from.frame_rate.frames_per_second();
+        let day = from.frame_rate.frames_per_day() as f64 * den as f64 / num as f64;
+        let offset = (to.to_seconds() - from.to_seconds()).rem_euclid(day);
+        if offset > day / 2.0 {
+            (offset - day) * 1000000.0
+        } else {
+            offset * 1000000.0
+        }
     }
 }
 
@@ -77,7 +984,7 @@ mod tests {
 
     #[test]
     fn add_progress() {
-        let time_const = TimecodeInstant::new(25);
+        let time_const = TimecodeInstant::new(FrameRate::Fps25);
         let mut time = time_const.clone();
         time.add_progress(0);
         assert_eq!(time, time_const);
@@ -90,7 +997,7 @@ mod tests {
     }
     #[test]
     fn add_sub_identity() {
-        let time_const = TimecodeInstant::new(25);
+        let time_const = TimecodeInstant::new(FrameRate::Fps25);
         for i in (0..36000 * 1000000).step_by(123456) {
             let mut time = time_const.clone();
             time.add_us(i);
@@ -98,4 +1005,451 @@ mod tests {
             assert_eq!(time, time_const, "Failed with {}us ({} s)", i, i / 1000000);
         }
     }
+    #[test]
+    fn add_sub_identity_drop_frame() {
+        for frame_rate in [FrameRate::Fps29_97Df, FrameRate::Fps59_94Df] {
+            let mut time_const = TimecodeInstant::new(frame_rate);
+            time_const.set_time(1, 9, 59, 17);
+            for i in (0..36000 * 1000000).step_by(123456) {
+                let mut time = time_const.clone();
+                time.add_us(i);
+                time.sub_us(i);
+                assert_eq!(time, time_const, "Failed with {}us ({} s)", i, i / 1000000);
+            }
+        }
+    }
+    #[test]
+    fn drop_frame_skips_frames() {
+        let mut time = TimecodeInstant::new(FrameRate::Fps29_97Df);
+        time.set_time(0, 0, 59, 29);
+        time.add_progress(u16::MAX);
+        time.add_progress(1);
+        assert_eq!(time.to_string(), "00:01:00;02");
+        time.set_time(0, 9, 59, 29);
+        time.add_progress(u16::MAX);
+        time.add_progress(1);
+        assert_eq!(time.to_string(), "00:10:00;00");
+
+        let mut time = TimecodeInstant::new(FrameRate::Fps59_94Df);
+        time.set_time(0, 1, 59, 59);
+        time.add_progress(u16::MAX);
+        time.add_progress(1);
+        assert_eq!(time.to_string(), "00:02:00;04");
+    }
+    #[test]
+    fn drop_frame_follows_wall_clock() {
+        for frame_rate in [FrameRate::Fps29_97Df, FrameRate::Fps59_94Df] {
+            let mut time = TimecodeInstant::new(frame_rate);
+            time.add_us(3600 * 1000000);
+            assert_eq!(time.to_string(), "01:00:00;00");
+
+            // One hour of 48 kHz audio in 256 sample periods.
+            let mut time = TimecodeInstant::new(frame_rate);
+            for _ in 0..225000 {
+                time.add_us(5333);
+                time.add_us(5333);
+                time.add_us(5334);
+            }
+            assert_eq!(time.to_string(), "01:00:00;00");
+        }
+    }
+    #[test]
+    fn pulldown_follows_wall_clock() {
+        let mut time = TimecodeInstant::new(FrameRate::Fps23_976);
+        time.add_us(3600 * 1000000);
+        assert_eq!(time.to_string(), "00:59:56:09");
+        let mut time = TimecodeInstant::new(FrameRate::Fps29_97);
+        time.add_us(1001000);
+        assert_eq!(time.to_string(), "00:00:01:00");
+    }
+    #[test]
+    fn frame_rate_serde() {
+        for rate in FrameRate::ALL {
+            let json = serde_json::to_string(&rate).unwrap();
+            assert_eq!(serde_json::from_str::<FrameRate>(&json).unwrap(), rate);
+        }
+        let time: TimecodeInstant =
+            serde_json::from_str(r#"{"frame_rate":30,"h":1,"m":2,"s":3,"f":4,"frame_progress":0}"#)
+                .unwrap();
+        assert_eq!(time.frame_rate, FrameRate::Fps30);
+        assert_eq!(time.to_string(), "01:02:03:04");
+        assert_eq!(
+            serde_json::from_str::<FrameRate>("0").unwrap(),
+            FrameRate::default()
+        );
+        let time: TimecodeInstant =
+            serde_json::from_str(r#"{"frame_rate":0,"h":0,"m":0,"s":0,"f":0,"frame_progress":0}"#)
+                .unwrap();
+        assert_eq!(time, TimecodeInstant::default());
+        assert!(serde_json::from_str::<FrameRate>("29").is_err());
+    }
+    #[test]
+    fn parse() {
+        let time: TimecodeInstant = "01:02:03:04".parse().unwrap();
+        assert_eq!(time.frame_rate, FrameRate::Fps25);
+        assert_eq!(time.to_string(), "01:02:03:04");
+        let time: TimecodeInstant = "00:10:00;00".parse().unwrap();
+        assert_eq!(time.frame_rate, FrameRate::Fps29_97Df);
+        assert_eq!(time.to_string(), "00:10:00;00");
+        let time = TimecodeInstant::parse(" 23:59:59.29 ", FrameRate::Fps30).unwrap();
+        assert_eq!(time.to_string(), "23:59:59:29");
+        let time = TimecodeInstant::parse("1:2:3:4", FrameRate::Fps59_94Df).unwrap();
+        assert_eq!(time.to_string(), "01:02:03;04");
+
+        for rate in FrameRate::ALL {
+            for frames in frame_counts(rate) {
+                let time = TimecodeInstant::from_frame_count(rate, frames);
+                assert_eq!(TimecodeInstant::parse(&time.to_string(), rate), Ok(time));
+            }
+        }
+    }
+
+    #[test]
+    fn parse_errors() {
+        for s in [
+            "",
+            "01:02:03",
+            "01:02:03:04:05",
+            "01;02:03:04",
+            "01.02:03:04",
+        ] {
+            assert_eq!(
+                s.parse::<TimecodeInstant>(),
+                Err(TimecodeParseError::InvalidFormat),
+                "{s}"
+            );
+        }
+        assert_eq!(
+            "01:0x:03:04".parse::<TimecodeInstant>(),
+            Err(TimecodeParseError::InvalidNumber {
+                field: "Minutes",
+                value: "0x".to_string()
+            })
+        );
+        assert_eq!(
+            "01:-2:03:04".parse::<TimecodeInstant>(),
+            Err(TimecodeParseError::InvalidNumber {
+                field: "Minutes",
+                value: "-2".to_string()
+            })
+        );
+        assert_eq!(
+            "24:00:00:00".parse::<TimecodeInstant>(),
+            Err(TimecodeParseError::OutOfRange {
+                field: "Hours",
+                value: 24,
+                limit: 24
+            })
+        );
+        assert_eq!(
+            "00:00:00:25".parse::<TimecodeInstant>(),
+            Err(TimecodeParseError::OutOfRange {
+                field: "Frames",
+                value: 25,
+                limit: 25
+            })
+        );
+        assert_eq!(
+            TimecodeInstant::parse("00:00:00;00", FrameRate::Fps30),
+            Err(TimecodeParseError::NotDropFrame(FrameRate::Fps30))
+        );
+        assert_eq!(
+            "00:01:00;01".parse::<TimecodeInstant>(),
+            Err(TimecodeParseError::DroppedFrame)
+        );
+        assert_eq!(
+            TimecodeInstant::parse("00:01:00:03", FrameRate::Fps59_94Df),
+            Err(TimecodeParseError::DroppedFrame)
+        );
+        assert!(TimecodeInstant::parse("00:01:00:04", FrameRate::Fps59_94Df).is_ok());
+    }
+
+    #[test]
+    fn user_bits_encodings() {
+        let bits = UserBits::from_chars(*b"R01B");
+        assert_eq!(bits.bits, 0x42313052);
+        assert_eq!(bits.group(1), 0x2);
+        assert_eq!(bits.group(2), 0x5);
+        assert_eq!(bits.group(0), 0);
+        assert_eq!(bits.group(9), 0);
+        assert_eq!(bits.chars(), *b"R01B");
+        assert_eq!(bits.binary_group_flags(), 0b001);
+
+        let date = UserBitsDate {
+            year: 26,
+            month: 10,
+            day: 18,
+            time_zone: 0x00,
+        };
+        let bits = UserBits::from_date(date);
+        assert_eq!(bits.bits, 0x00261018);
+        assert_eq!(bits.date(), Some(date));
+        assert_eq!(bits.binary_group_flags(), 0b100);
+        assert_eq!(UserBits::raw(0x0026101A).date(), None);
+        assert_eq!(UserBits::raw(0x00261318).date(), None);
+
+        for flags in 0..8 {
+            assert_eq!(
+                UserBits::from_binary_group_flags(0, flags).binary_group_flags(),
+                flags
+            );
+        }
+    }
+
+    /// Frame counts spread over the whole day, with the edges around midnight.
+    fn frame_counts(frame_rate: FrameRate) -> impl Iterator<Item = i64> {
+        let day = frame_rate.frames_per_day();
+        (0..day).step_by(7919).chain([0, 1, day - 2, day - 1])
+    }
+
+    #[test]
+    fn frame_count_round_trip() {
+        for rate in FrameRate::ALL {
+            for frames in frame_counts(rate) {
+                let time = TimecodeInstant::from_frame_count(rate, frames);
+                assert_eq!(time.frame_count(), frames, "{rate} {time}");
+                assert!(time.h < 24, "{rate} {time}");
+                assert_eq!(
+                    TimecodeInstant::from_frame_count(rate, frames + rate.frames_per_day()),
+                    time
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn ordering_follows_frame_count() {
+        for rate in [FrameRate::Fps25, FrameRate::Fps29_97Df] {
+            let times: Vec<TimecodeInstant> = frame_counts(rate)
+                .map(|frames| TimecodeInstant::from_frame_count(rate, frames))
+                .collect();
+            for a in times.iter().step_by(13) {
+                for b in &times {
+                    assert_eq!(a.cmp(b), a.frame_count().cmp(&b.frame_count()));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn duration_arithmetic() {
+        for rate in FrameRate::ALL {
+            let day = rate.frames_per_day();
+            for frames in frame_counts(rate).step_by(5) {
+                let a = TimecodeInstant::from_frame_count(rate, frames);
+                for delta in [-day - 3, -12345, -1, 0, 1, 98765, day + 7] {
+                    let d = TimecodeDuration::new(rate, delta);
+                    assert_eq!(a.clone() + d - d, a, "{rate} {a} {d}");
+                    assert_eq!(
+                        (a.clone() + d).frame_count(),
+                        (frames + delta).rem_euclid(day)
+                    );
+                    let b = a.clone() + d;
+                    assert_eq!(b.clone() + (&a - &b), a, "{rate} {a} {b}");
+                    assert_eq!(a.until(&b).frames, delta.rem_euclid(day));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn duration_conversions() {
+        let mut target = TimecodeInstant::new(FrameRate::Fps25);
+        target.set_time(1, 0, 30, 0);
+        let mut now = TimecodeInstant::new(FrameRate::Fps25);
+        now.set_time(1, 0, 0, 12);
+        let remaining = target.clone() - now.clone();
+        assert_eq!(remaining.to_string(), "00:00:29:13");
+        assert_eq!(remaining.to_us(), 29520000);
+        assert_eq!((now.clone() - target.clone()).to_string(), "-00:00:29:13");
+        assert_eq!(target.until(&now).to_string(), "23:59:30:12");
+
+        // Durations don't wrap at 24 hours.
+        let day = FrameRate::Fps25.frames_per_day();
+        let d = TimecodeDuration::new(FrameRate::Fps25, day + 7);
+        assert_eq!(d.to_string(), "24:00:00:07");
+        assert_eq!((-d - d).to_string(), "-48:00:00:14");
+        let day = FrameRate::Fps29_97Df.frames_per_day();
+        let d = TimecodeDuration::new(FrameRate::Fps29_97Df, day + 1800);
+        assert_eq!(d.to_string(), "24:01:00;02");
+
+        let d = TimecodeDuration::from_us(FrameRate::Fps29_97, 1001000);
+        assert_eq!(d.frames, 30);
+        assert_eq!(d.to_us(), 1001000);
+        assert_eq!(d.frames_at(FrameRate::Fps60), 60);
+        assert_eq!((d + TimecodeDuration::new(FrameRate::Fps60, 60)).frames, 59);
+    }
+
+    #[test]
+    fn seconds_and_samples() {
+        for rate in FrameRate::ALL {
+            for frames in frame_counts(rate) {
+                let time = TimecodeInstant::from_frame_count(rate, frames);
+                for sample_rate in [44100, 48000, 96000] {
+                    let samples = time.to_samples(sample_rate);
+                    assert_eq!(
+                        TimecodeInstant::from_samples(rate, samples, sample_rate),
+                        time,
+                        "{rate} {time} {sample_rate}"
+                    );
+                }
+                let back = TimecodeInstant::from_seconds(rate, time.to_seconds());
+                assert_eq!(back, time, "{rate} {time}");
+            }
+        }
+        let mut time = TimecodeInstant::new(FrameRate::Fps29_97Df);
+        time.set_time(1, 0, 0, 0);
+        assert_eq!(time.to_samples(48000), 172799828);
+        assert!((time.to_seconds() - 3599.9964).abs() < 0.0001);
+    }
+
+    #[test]
+    fn frame_number_round_trip() {
+        let mut time = TimecodeInstant::new(FrameRate::Fps29_97Df);
+        for frame in 0..24 * 107892 {
+            time.set_frame_count(frame);
+            assert_eq!(time.frame_count(), frame, "Failed at {time}");
+            assert!(time.m % 10 == 0 || time.s != 0 || time.f >= 2, "{time}");
+        }
+    }
+
+    fn feed_frames(
+        sync: &mut TimecodeSynchronizer,
+        time: &mut TimecodeInstant,
+        now_us: &mut u64,
+        frames: usize,
+        frame_us: u64,
+    ) {
+        for _ in 0..frames {
+            sync.feed(time, *now_us);
+            time.add_us(40000);
+            *now_us += frame_us;
+            sync.tick(*now_us);
+        }
+    }
+
+    #[test]
+    fn sync_locks_and_freewheels() {
+        let mut sync = TimecodeSynchronizer::new(SyncConfiguration::default());
+        let mut time: TimecodeInstant = "00:59:59:00".parse().unwrap();
+        let mut now_us = 1000000;
+        assert_eq!(sync.tick(now_us), LockState::Unlocked);
+        assert_eq!(sync.position(now_us), None);
+
+        feed_frames(&mut sync, &mut time, &mut now_us, 3, 40000);
+        assert_eq!(sync.state, LockState::Locking);
+        feed_frames(&mut sync, &mut time, &mut now_us, 1, 40000);
+        assert_eq!(sync.state, LockState::Locked);
+
+        // Two frames lost on the cable keep the lock.
+        time.add_us(80000);
+        now_us += 80000;
+        feed_frames(&mut sync, &mut time, &mut now_us, 1, 40000);
+        assert_eq!(sync.state, LockState::Locked);
+        assert_eq!(sync.position(now_us), Some(time.clone()));
+
+        now_us += 120000;
+        assert_eq!(sync.tick(now_us), LockState::Freewheeling);
+        time.add_us(120000);
+        assert_eq!(sync.position(now_us), Some(time.clone()));
+        now_us += 25 * 40000;
+        assert_eq!(sync.tick(now_us), LockState::Unlocked);
+        assert_eq!(sync.position(now_us), None);
+    }
+
+    #[test]
+    fn sync_relocks_on_jump() {
+        let mut sync = TimecodeSynchronizer::new(SyncConfiguration::default());
+        let mut time: TimecodeInstant = "23:59:59:20".parse().unwrap();
+        let mut now_us = 0;
+        feed_frames(&mut sync, &mut time, &mut now_us, 10, 40000);
+        assert_eq!(sync.state, LockState::Locked);
+        assert_eq!(time.to_string(), "00:00:00:05");
+
+        time.set_time(2, 0, 0, 0);
+        feed_frames(&mut sync, &mut time, &mut now_us, 1, 40000);
+        assert_eq!(sync.state, LockState::Locking);
+        feed_frames(&mut sync, &mut time, &mut now_us, 3, 40000);
+        assert_eq!(sync.state, LockState::Locked);
+    }
+
+    #[test]
+    fn sync_one_shot_jam() {
+        let mut sync = TimecodeSynchronizer::new(SyncConfiguration {
+            jam_sync: JamSyncMode::OneShot,
+            ..Default::default()
+        });
+        let mut time: TimecodeInstant = "10:00:00:00".parse().unwrap();
+        let mut now_us = 0;
+        feed_frames(&mut sync, &mut time, &mut now_us, 4, 40000);
+        assert_eq!(sync.state, LockState::Locked);
+        let expected = sync.position(now_us + 3600 * 1000000).unwrap();
+
+        let mut other: TimecodeInstant = "05:00:00:00".parse().unwrap();
+        feed_frames(&mut sync, &mut other, &mut now_us, 10, 40000);
+        assert_eq!(sync.state, LockState::Locked);
+        now_us += 3600 * 1000000 - 10 * 40000;
+        assert_eq!(sync.tick(now_us), LockState::Freewheeling);
+        assert_eq!(sync.position(now_us), Some(expected));
+    }
+
+    #[test]
+    fn sync_drift() {
+        let mut sync = TimecodeSynchronizer::new(SyncConfiguration::default());
+        let mut time: TimecodeInstant = "01:00:00:00".parse().unwrap();
+        let mut now_us = 0;
+        // Source frames arrive 400 us early, i.e. the source runs 1 % fast, so it gains 40 ms
+        // over every 100 frames. Smoothing holds the reported drift about 10 frames behind.
+        feed_frames(&mut sync, &mut time, &mut now_us, 101, 39600);
+        assert_eq!(sync.state, LockState::Locked);
+        let status = sync.status();
+        assert!((35000..=37000).contains(&status.drift_us), "{status:?}");
+        feed_frames(&mut sync, &mut time, &mut now_us, 150, 39600);
+        let status = sync.status();
+        assert!((95000..=97000).contains(&status.drift_us), "{status:?}");
+
+        // A source that starts arriving 5 ms late stays 5 ms behind the local clock.
+        let mut sync = TimecodeSynchronizer::new(SyncConfiguration::default());
+        feed_frames(&mut sync, &mut time, &mut now_us, 10, 40000);
+        assert_eq!(sync.status().drift_us, 0);
+        now_us += 5000;
+        feed_frames(&mut sync, &mut time, &mut now_us, 250, 40000);
+        assert_eq!(sync.state, LockState::Locked);
+        let status = sync.status();
+        assert!((-5001..=-4999).contains(&status.drift_us), "{status:?}");
+
+        // Relocking after a jump measures from the new position.
+        time.set_time(2, 0, 0, 0);
+        feed_frames(&mut sync, &mut time, &mut now_us, 10, 40000);
+        assert_eq!(sync.status().drift_us, 0);
+    }
+
+    #[test]
+    fn advance_with_playrate() {
+        let start: TimecodeInstant = "01:00:00:00".parse().unwrap();
+
+        let mut time = start.clone();
+        time.advance(10 * 1000000, 80, TimecodePlayrateMode::FollowMusicalTime);
+        assert_eq!(time.to_string(), "01:00:08:00");
+        let mut time = start.clone();
+        time.advance(10 * 1000000, 80, TimecodePlayrateMode::FollowWallClock);
+        assert_eq!(time.to_string(), "01:00:10:00");
+        let mut time = start.clone();
+        time.advance(10 * 1000000, 80, TimecodePlayrateMode::Freeze);
+        assert_eq!(time, start);
+        time.advance(10 * 1000000, 100, TimecodePlayrateMode::Freeze);
+        assert_eq!(time.to_string(), "01:00:10:00");
+
+        // An hour of 256 sample periods at 48 kHz, rehearsing at 83 %.
+        let mut time = TimecodeInstant::new(FrameRate::Fps29_97Df);
+        for _ in 0..225000 {
+            for period_us in [5333, 5333, 5334] {
+                time.advance(period_us, 83, TimecodePlayrateMode::FollowMusicalTime);
+            }
+        }
+        let mut expected = TimecodeInstant::new(FrameRate::Fps29_97Df);
+        expected.add_us(83 * 36 * 1000000);
+        assert_eq!(time, expected);
+        assert_eq!(time.frame_progress, expected.frame_progress);
+    }
 }
@@ -0,0 +1,175 @@
+use serde::{Deserialize, Serialize};
+
+use crate::cue::{Cue, EventOffset};
+
+/// Absolute beat and bar timing of a cue played straight through, ignoring jumps and pauses.
+///
+/// The map is a snapshot and nothing invalidates it. Whoever holds it owns keeping it current,
+/// by calling `rebuild` after changing the cue's beats or tempo events.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct TimeMap {
+    /// Start of every beat in microseconds from the start of the cue, followed by the end of the
+    /// last beat.
+    beat_starts: Vec<u64>,
+    /// Bar number and first beat index of every bar, in cue order.
+    bars: Vec<(usize, usize)>,
+}
+
+impl TimeMap {
+    pub fn new(cue: &Cue) -> TimeMap {
+        let mut map = TimeMap::default();
+        map.rebuild(cue);
+        map
+    }
+
+    pub fn rebuild(&mut self, cue: &Cue) {
+        self.beat_starts.clear();
+        self.bars.clear();
+        let mut time = 0;
+        self.beat_starts.push(time);
+        for (idx, beat) in cue.beats.iter().enumerate() {
+            time += beat.length as u64;
+            self.beat_starts.push(time);
+            if self
+                .bars
+                .last()
+                .is_none_or(|(bar, _)| *bar != beat.bar_number)
+            {
+                self.bars.push((beat.bar_number, idx));
+            }
+        }
+    }
+
+    pub fn beat_count(&self) -> usize {
+        self.beat_starts.len() - 1
+    }
+
+    /// Length of the cue in microseconds.
+    pub fn duration(&self) -> u64 {
+        *self.beat_starts.last().unwrap_or(&0)
+    }
+
+    pub fn beat_start(&self, beat_idx: usize) -> Option<u64> {
+        if beat_idx >= self.beat_count() {
+            return None;
+        }
+        Some(self.beat_starts[beat_idx])
+    }
+
+    pub fn beat_end(&self, beat_idx: usize) -> Option<u64> {
+        if beat_idx >= self.beat_count() {
+            return None;
+        }
+        Some(self.beat_starts[beat_idx + 1])
+    }
+
+    /// Time an event at `offset` in the given beat fires, in microseconds from the start of the
+    /// cue.
+    pub fn event_time(&self, beat_idx: usize, offset: EventOffset) -> Option<u64> {
+        let start = self.beat_start(beat_idx)?;
+        let length = self.beat_starts[beat_idx + 1] - start;
+        Some(start + offset.offset_us(length as usize))
+    }
+
+    /// Beat playing at `time_us`, or `None` at or after the end of the cue.
+    pub fn beat_at(&self, time_us: u64) -> Option<usize> {
+        if time_us >= self.duration() {
+            return None;
+        }
+        Some(self.beat_starts.partition_point(|start| *start <= time_us) - 1)
+    }
+
+    /// Bar number playing at `time_us`.
+    pub fn bar_at(&self, time_us: u64) -> Option<usize> {
+        let beat_idx = self.beat_at(time_us)?;
+        let bar = self.bars.partition_point(|(_, first)| *first <= beat_idx) - 1;
+        Some(self.bars[bar].0)
+    }
+
+    /// First beat of the given bar. Assumes bar numbers never decrease through the cue, as
+    /// `Cue::reorder_numbers` leaves them.
+    pub fn bar_start(&self, bar_number: usize) -> Option<usize> {
+        let bar = self
+            .bars
+            .partition_point(|(number, _)| *number < bar_number);
+        match self.bars.get(bar) {
+            Some((number, first)) if *number == bar_number => Some(*first),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cue::{Beat, BeatEvent};
+
+    fn cue() -> Cue {
+        let mut cue = Cue::empty();
+        for i in 0..16 {
+            cue.beats.push(Beat {
+                count: (i % 4 + 1) as u8,
+                bar_number: i / 4 + 1,
+                length: 0,
+                events: vec![],
+            });
+        }
+        cue.beats[0]
+            .events
+            .push(BeatEvent::TempoChangeEvent { tempo: 120 });
+        cue.beats[8]
+            .events
+            .push(BeatEvent::TempoChangeEvent { tempo: 60 });
+        cue.recalculate_tempo_changes();
+        cue
+    }
+
+    #[test]
+    fn beat_times() {
+        let map = TimeMap::new(&cue());
+        assert_eq!(map.beat_count(), 16);
+        assert_eq!(map.duration(), 8 * 500000 + 8 * 1000000);
+        assert_eq!(map.beat_start(0), Some(0));
+        assert_eq!(map.beat_start(8), Some(4000000));
+        assert_eq!(map.beat_end(15), Some(12000000));
+        assert_eq!(map.beat_start(16), None);
+
+        assert_eq!(map.beat_at(0), Some(0));
+        assert_eq!(map.beat_at(499999), Some(0));
+        assert_eq!(map.beat_at(500000), Some(1));
+        assert_eq!(map.beat_at(4999999), Some(8));
+        assert_eq!(map.beat_at(11999999), Some(15));
+        assert_eq!(map.beat_at(12000000), None);
+
+        let and = EventOffset::Fraction {
+            numerator: 1,
+            denominator: 2,
+        };
+        assert_eq!(map.event_time(2, and), Some(1250000));
+        assert_eq!(map.event_time(8, and), Some(4500000));
+        assert_eq!(
+            map.event_time(8, EventOffset::Microseconds(20000)),
+            Some(4020000)
+        );
+        assert_eq!(map.event_time(16, EventOffset::Start), None);
+    }
+
+    #[test]
+    fn bars() {
+        let map = cue().time_map();
+        assert_eq!(map.bar_start(1), Some(0));
+        assert_eq!(map.bar_start(3), Some(8));
+        assert_eq!(map.bar_start(5), None);
+        assert_eq!(map.bar_at(0), Some(1));
+        assert_eq!(map.bar_at(3999999), Some(2));
+        assert_eq!(map.bar_at(4000000), Some(3));
+    }
+
+    #[test]
+    fn empty_cue() {
+        let map = TimeMap::new(&Cue::empty());
+        assert_eq!(map.duration(), 0);
+        assert_eq!(map.beat_at(0), None);
+        assert_eq!(map.bar_start(1), None);
+    }
+}